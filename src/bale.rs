@@ -0,0 +1,839 @@
+use crate::crusher::Crusher;
+use crate::serproxy::CarriageSer;
+use anyhow::Result;
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use serde::ser::{self, Serialize};
+use serde::{Deserialize, Deserializer, Serializer};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"FORKBALE";
+const VERSION: u16 = 6;
+
+/// A compact binary cache. Fields are written in declaration order without names, integers as
+/// LEB128 varints, and every string only once: repeats refer back to the first occurrence, which
+/// takes care of the many dates, licenses and requirements the dump keeps repeating.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bale;
+
+impl Crusher for Bale {
+    type Floam = CarriageSer;
+
+    fn crush<P: AsRef<Path>>(&self, file: P, contents: &Self::FloamSer) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(file)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&to_bytes(contents)?)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn uncrush(contents: Vec<u8>) -> Result<Self::Floam> {
+        Ok(from_bytes(&contents)?)
+    }
+}
+
+pub fn to_bytes<T: Serialize>(value: &T) -> Result<Vec<u8>, BaleError> {
+    let mut baler = Baler::default();
+    baler.output.extend_from_slice(MAGIC);
+    baler.output.extend_from_slice(&VERSION.to_le_bytes());
+    value.serialize(&mut baler)?;
+    Ok(baler.output)
+}
+
+pub fn from_bytes<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T, BaleError> {
+    let rest = input
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| BaleError::new("not a bale, the header is missing"))?;
+    let (version, rest) = rest
+        .split_first_chunk::<2>()
+        .ok_or_else(|| BaleError::new("truncated header"))?;
+    let version = u16::from_le_bytes(*version);
+    if version != VERSION {
+        return Err(BaleError(format!(
+            "bale version {version} is not supported, expected {VERSION}"
+        )));
+    }
+
+    let mut unbaler = Unbaler {
+        input: rest,
+        strings: Vec::new(),
+    };
+    let value = T::deserialize(&mut unbaler)?;
+    if unbaler.input.is_empty() {
+        Ok(value)
+    } else {
+        Err(BaleError::new("trailing bytes after the bale"))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BaleError(String);
+
+impl BaleError {
+    fn new(message: &str) -> Self {
+        Self(message.to_owned())
+    }
+}
+
+impl std::error::Error for BaleError {}
+
+impl Display for BaleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ser::Error for BaleError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for BaleError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+#[derive(Default)]
+struct Baler {
+    output: Vec<u8>,
+    strings: HashMap<String, u64>,
+}
+
+impl Baler {
+    fn write_varint(&mut self, mut value: u64) {
+        loop {
+            #[allow(clippy::cast_possible_truncation)]
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.output.push(byte);
+                return;
+            }
+            self.output.push(byte | 0x80);
+        }
+    }
+
+    fn write_len(&mut self, len: usize) {
+        self.write_varint(len as u64);
+    }
+
+    /// Writes `0` and the string the first time it comes up, its position in the table plus one
+    /// every time after.
+    fn write_str(&mut self, value: &str) {
+        if let Some(&index) = self.strings.get(value) {
+            self.write_varint(index + 1);
+        } else {
+            self.strings
+                .insert(value.to_owned(), self.strings.len() as u64);
+            self.write_varint(0);
+            self.write_len(value.len());
+            self.output.extend_from_slice(value.as_bytes());
+        }
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    #[allow(clippy::cast_sign_loss)]
+    let encoded = ((value << 1) ^ (value >> 63)) as u64;
+    encoded
+}
+
+fn unzigzag(value: u64) -> i64 {
+    #[allow(clippy::cast_possible_wrap)]
+    let decoded = (value >> 1) as i64 ^ -((value & 1) as i64);
+    decoded
+}
+
+impl Serializer for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), BaleError> {
+        self.output.push(u8::from(v));
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), BaleError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), BaleError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), BaleError> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), BaleError> {
+        self.write_varint(zigzag(v));
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), BaleError> {
+        self.output.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), BaleError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), BaleError> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), BaleError> {
+        self.write_varint(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), BaleError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), BaleError> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), BaleError> {
+        self.serialize_u32(v.into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), BaleError> {
+        self.write_str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), BaleError> {
+        self.write_len(v.len());
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), BaleError> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), BaleError> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), BaleError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), BaleError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), BaleError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), BaleError> {
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, BaleError> {
+        let len = len.ok_or_else(|| BaleError::new("sequences need a known length"))?;
+        self.write_len(len);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, BaleError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, BaleError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, BaleError> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, BaleError> {
+        let len = len.ok_or_else(|| BaleError::new("maps need a known length"))?;
+        self.write_len(len);
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, BaleError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, BaleError> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl ser::SerializeSeq for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), BaleError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Baler {
+    type Ok = ();
+    type Error = BaleError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), BaleError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+}
+
+struct Unbaler<'de> {
+    input: &'de [u8],
+    strings: Vec<&'de str>,
+}
+
+impl<'de> Unbaler<'de> {
+    fn take(&mut self, len: usize) -> Result<&'de [u8], BaleError> {
+        if self.input.len() < len {
+            return Err(BaleError::new("unexpected end of the bale"));
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, BaleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_varint(&mut self) -> Result<u64, BaleError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BaleError::new("varint overflows 64 bits"))
+    }
+
+    fn read_len(&mut self) -> Result<usize, BaleError> {
+        usize::try_from(self.read_varint()?).map_err(|_| BaleError::new("length out of range"))
+    }
+
+    fn read_str(&mut self) -> Result<&'de str, BaleError> {
+        match self.read_len()? {
+            0 => {
+                let len = self.read_len()?;
+                let value =
+                    std::str::from_utf8(self.take(len)?).map_err(|e| BaleError(e.to_string()))?;
+                self.strings.push(value);
+                Ok(value)
+            }
+            index => self
+                .strings
+                .get(index - 1)
+                .copied()
+                .ok_or_else(|| BaleError::new("string refers past the table")),
+        }
+    }
+
+    fn read_bool(&mut self) -> Result<bool, BaleError> {
+        match self.read_byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(BaleError::new("invalid bool")),
+        }
+    }
+
+    fn read_unsigned<T: TryFrom<u64>>(&mut self) -> Result<T, BaleError> {
+        T::try_from(self.read_varint()?).map_err(|_| BaleError::new("integer out of range"))
+    }
+
+    fn read_signed<T: TryFrom<i64>>(&mut self) -> Result<T, BaleError> {
+        T::try_from(unzigzag(self.read_varint()?))
+            .map_err(|_| BaleError::new("integer out of range"))
+    }
+}
+
+impl<'de> Deserializer<'de> for &mut Unbaler<'de> {
+    type Error = BaleError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, BaleError> {
+        Err(BaleError::new("bales are not self-describing"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_bool(self.read_bool()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_i8(self.read_signed()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_i16(self.read_signed()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_i32(self.read_signed()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_i64(self.read_signed()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_u8(self.read_byte()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_u16(self.read_unsigned()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_u32(self.read_unsigned()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_u64(self.read_varint()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let bytes = self
+            .take(4)?
+            .try_into()
+            .map_err(|_| BaleError::new("f32"))?;
+        visitor.visit_f32(f32::from_le_bytes(bytes))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let bytes = self
+            .take(8)?
+            .try_into()
+            .map_err(|_| BaleError::new("f64"))?;
+        visitor.visit_f64(f64::from_le_bytes(bytes))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let c = char::from_u32(self.read_unsigned()?).ok_or_else(|| BaleError::new("char"))?;
+        visitor.visit_char(c)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_borrowed_str(self.read_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let len = self.read_len()?;
+        visitor.visit_borrowed_bytes(self.take(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        if self.read_bool()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Bundle {
+            unbaler: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        visitor.visit_seq(Bundle {
+            unbaler: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        let len = self.read_len()?;
+        visitor.visit_map(Bundle {
+            unbaler: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, BaleError> {
+        visitor.visit_u32(self.read_unsigned()?)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, BaleError> {
+        Err(BaleError::new("bales cannot skip values"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// A sequence or map of known length inside the bale.
+struct Bundle<'a, 'de> {
+    unbaler: &'a mut Unbaler<'de>,
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for Bundle<'_, 'de> {
+    type Error = BaleError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, BaleError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.unbaler).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> MapAccess<'de> for Bundle<'_, 'de> {
+    type Error = BaleError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, BaleError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.unbaler).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, BaleError> {
+        seed.deserialize(&mut *self.unbaler)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut Unbaler<'de> {
+    type Error = BaleError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), BaleError> {
+        let index: u32 = self.read_unsigned()?;
+        let value = seed.deserialize(IntoDeserializer::<BaleError>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Unbaler<'de> {
+    type Error = BaleError;
+
+    fn unit_variant(self) -> Result<(), BaleError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, BaleError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, BaleError> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, BaleError> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind {
+        Normal,
+        Renamed(String),
+        Pinned { version: String },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        id: u32,
+        offset: i64,
+        name: String,
+        license: String,
+        previous: Option<String>,
+        missing: Option<u64>,
+        yanked: bool,
+        kinds: Vec<Kind>,
+        features: BTreeMap<String, Vec<String>>,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            id: 300,
+            offset: -70_000,
+            name: "serde".to_owned(),
+            license: "MIT OR Apache-2.0".to_owned(),
+            previous: Some("MIT OR Apache-2.0".to_owned()),
+            missing: None,
+            yanked: true,
+            kinds: vec![
+                Kind::Normal,
+                Kind::Renamed("serde".to_owned()),
+                Kind::Pinned {
+                    version: "1.0.200".to_owned(),
+                },
+            ],
+            features: BTreeMap::from([(
+                "derive".to_owned(),
+                vec!["serde_derive".to_owned(), "serde".to_owned()],
+            )]),
+        }
+    }
+
+    #[test]
+    fn round_trips_and_interns_strings() -> Result<(), BaleError> {
+        let bytes = to_bytes(&sample())?;
+        assert_eq!(from_bytes::<Sample>(&bytes)?, sample());
+
+        let occurrences = bytes
+            .windows("MIT OR Apache-2.0".len())
+            .filter(|window| *window == b"MIT OR Apache-2.0")
+            .count();
+        assert_eq!(occurrences, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_versions_and_foreign_bytes() -> Result<(), BaleError> {
+        let mut bytes = to_bytes(&sample())?;
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = from_bytes::<Sample>(&bytes).unwrap_err();
+        assert!(error.to_string().contains("not supported"), "{error}");
+
+        assert!(from_bytes::<Sample>(b"(waybill: ())").is_err());
+        let mut truncated = to_bytes(&sample())?;
+        truncated.pop();
+        assert!(from_bytes::<Sample>(&truncated).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use forklift::{Config, Engine, Format, Query};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::fs;
+use std::path::PathBuf;
+
+const HELP: &str = "\
+queries:
+  LIFT <crate> [WHERE <conditions>] [REVERSE] [DEPTH <n>] [FEATURES <a,b>]
+               [NO-DEFAULT-FEATURES] [TARGET <triple>]
+
+commands:
+  :help              show this message
+  :format tree|json  switch the output format
+  :reload            rebuild the graph from the dump
+  :quit              leave the session";
+
+/// The interactive session: the `Carriage` is loaded once and queries are read line by line
+/// until the input ends.
+pub struct Cabin {
+    engine: Engine,
+    editor: Editor<CrateNames, FileHistory>,
+    history: PathBuf,
+}
+
+impl Cabin {
+    pub fn new(engine: Engine) -> Result<Self> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(CrateNames::new(engine.crate_names())));
+        let history = Config::history_path();
+        // A missing history file only means this is the first session.
+        let _ = editor.load_history(&history);
+        Ok(Self {
+            engine,
+            editor,
+            history,
+        })
+    }
+
+    pub fn run(mut self) -> Result<()> {
+        loop {
+            let line = match self.editor.readline("forklift> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            self.editor.add_history_entry(line)?;
+            match self.operate(line) {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Quit) => break,
+                Err(e) => eprintln!("error: {e:#}"),
+            }
+        }
+        if let Some(dir) = self.history.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.editor.save_history(&self.history)?;
+        Ok(())
+    }
+
+    fn operate(&mut self, line: &str) -> Result<Flow> {
+        let Some(command) = line.strip_prefix(':') else {
+            let query = Query::parse(line).map_err(|e| anyhow!(e.diagnose(line)))?;
+            let results = self.engine.query(&query);
+            self.engine.process_output(results.as_ref())?;
+            return Ok(Flow::Continue);
+        };
+
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("help" | "h"), None) => println!("{HELP}"),
+            (Some("format"), Some(format)) => match Format::from_str(format, true) {
+                Ok(format) => self.engine.set_format(format),
+                Err(_) => eprintln!("unknown format `{format}`, expected tree or json"),
+            },
+            (Some("reload"), None) => {
+                self.engine.reload()?;
+                if let Some(helper) = self.editor.helper_mut() {
+                    *helper = CrateNames::new(self.engine.crate_names());
+                }
+                eprintln!("reloaded");
+            }
+            (Some("quit" | "q"), None) => return Ok(Flow::Quit),
+            _ => eprintln!("unknown command `:{command}`, try :help"),
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Completes the word under the cursor with crate names from the `Lookup`.
+#[derive(Helper, Highlighter, Hinter, Validator)]
+struct CrateNames {
+    names: Vec<String>,
+}
+
+impl CrateNames {
+    fn new(names: Vec<String>) -> Self {
+        Self { names }
+    }
+}
+
+impl Completer for CrateNames {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        if prefix.is_empty() || prefix.starts_with(':') {
+            return Ok((start, Vec::new()));
+        }
+
+        let first = self.names.partition_point(|name| name.as_str() < prefix);
+        let candidates = self.names[first..]
+            .iter()
+            .take_while(|name| name.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok((start, candidates))
+    }
+}
@@ -1,166 +1,627 @@
 use crate::cell::SichtCell;
-use crate::lookup::Lookup;
-use crate::store::Skid;
-use crate::store::{Cdv, Crate, Depencil, Kiste, Lesart, UnrolledCrate};
-use anyhow::Result;
-use csv::Reader;
-use flate2::read::GzDecoder;
+use crate::conveyor::Conveyor;
+use crate::index::{IndexDelta, IndexRelease, IndexSource};
+use crate::lookup::{Lookup, canonical, crate_key};
+use crate::registry::{self, Registry};
+use crate::store::{Crate, Kiste, Release, Skid};
+use crate::tally::{TableTally, Tally};
+use crate::waybill::Waybill;
+use anyhow::{Context, Result, anyhow, bail};
+use semver::Version;
 use sicht::SichtMap;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
-use std::fs::File;
 use std::path::Path;
-use tar::Archive;
 
 #[derive(Clone, Debug, Default)]
 pub struct Carriage {
     pub map: SichtCell<SichtMap<u32, String, Crate>>,
-    pub traversed: SichtCell<SichtMap<u32, String, Skid>>,
     pub lookup: SichtCell<Lookup>,
+    pub waybill: Waybill,
 }
 
-impl<'a> Carriage {
+impl Carriage {
     pub fn new(map: SichtCell<SichtMap<u32, String, Crate>>, lookup: Lookup) -> Self {
         Self {
             map,
-            traversed: SichtCell::default(),
             lookup: SichtCell::new(lookup),
+            waybill: Waybill::default(),
         }
     }
 
+    /// The graph of `map`, its crates registered in the lookup by name.
     pub fn from_map(map: SichtMap<u32, String, Crate>) -> Self {
-        Self {
-            map: SichtCell::new(map),
-            traversed: SichtCell::default(),
-            lookup: SichtCell::default(),
-        }
+        let lookup = Lookup::with_krate(map.iter().map(|(_, krate): (&u32, &Crate)| &krate.krate));
+        Self::new(SichtCell::new(map), lookup)
     }
 
-    pub fn unarchive<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(path)?;
-        let mut archive = Archive::new(GzDecoder::new(file));
-        let cdv = archive.entries()?.fold(Cdv::default(), |mut cdv, e| {
-            match e {
-                Ok(e)
-                    if let Ok(p) = e.path()
-                        && p.ends_with("crates.csv") =>
-                {
-                    cdv.crates = Reader::from_reader(e)
-                        .deserialize::<Kiste>()
-                        .filter_map(Result::ok)
-                        .map(|cr| {
-                            let name = cr.name.clone();
-                            (cr.id, name, Crate::new(cr.clone()))
-                        })
-                        .collect::<SichtMap<u32, String, Crate>>();
-                }
-                Ok(e)
-                    if let Ok(p) = e.path()
-                        && p.ends_with("dependencies.csv") =>
-                {
-                    cdv.dependencies = Reader::from_reader(e)
-                        .deserialize::<Depencil>()
-                        .filter_map(Result::ok)
-                        .fold(BTreeMap::<u32, Vec<u32>>::default(), |mut deps, dep| {
-                            deps.entry(dep.version_id)
-                                .and_modify(|v| v.push(dep.crate_id))
-                                .or_insert_with(|| vec![dep.crate_id]);
-                            deps
-                        });
-                }
+    /// Loads the dump, settling its tally: rows that don't fit are reported, or fail the load
+    /// if `strict`.
+    pub fn unarchive<P: AsRef<Path>>(path: P, strict: bool) -> Result<Self> {
+        let (carriage, tally) = Self::unarchive_tallied(path, false)?;
+        tally.settle(strict)?;
+        Ok(carriage)
+    }
 
-                Ok(e)
-                    if let Ok(p) = e.path()
-                        && p.ends_with("versions.csv") =>
-                {
-                    cdv.versions = Reader::from_reader(e)
-                        .deserialize::<Lesart>()
-                        .filter_map(Result::ok)
-                        .filter_map(|ver| ver.crate_id.map(|c_id| (ver.id, c_id)))
-                        .collect::<BTreeMap<u32, u32>>();
-                }
+    /// Loads the dump along with what became of its rows, drawing the progress on stderr if
+    /// `progress`.
+    pub fn unarchive_tallied<P: AsRef<Path>>(path: P, progress: bool) -> Result<(Self, Tally)> {
+        let cdv = Conveyor::unload(path.as_ref(), progress)?;
+        let waybill = Waybill::for_dump(&path, cdv.dumped_at.clone())?;
+        let (mut carriage, tally) = cdv.process_to_carriage();
+        carriage.waybill = Waybill {
+            clean: tally.is_clean(),
+            ..waybill
+        };
+        Ok((carriage, tally))
+    }
 
-                _ => {}
+    /// Brings the graph up to date with a registry index: releases the dump doesn't have yet are
+    /// added and yanks flipped since are applied. Only `names` are looked at, or every crate a
+    /// checkout has a file for. A mirror can't be listed and asking it for every crate known
+    /// would take as many requests, so it needs `names`. The index belongs to `origin`, `None`
+    /// being crates.io, and `registries` settles edges pointing elsewhere.
+    pub fn apply_index(
+        &self,
+        source: &IndexSource,
+        names: &[String],
+        origin: Option<&str>,
+        registries: &[Registry],
+    ) -> Result<IndexDelta> {
+        let names = if names.is_empty() {
+            let Some(names) = source.crate_names() else {
+                bail!("a sparse mirror can't be listed, name the crates to refresh from it");
+            };
+            names
+        } else {
+            // The index files go by the published name, `Serde-JSON` has to be fetched as
+            // `serde_json`. Names the graph doesn't know yet are taken as they are spelled.
+            let map = self.map.borrow();
+            let lookup = self.lookup.borrow();
+            names
+                .iter()
+                .map(|name| {
+                    Self::crate_in_registry(&map, &lookup, origin, name)
+                        .map_or_else(|| name.clone(), |krate| krate.krate.name.clone())
+                })
+                .collect()
+        };
+
+        let mut releases = Vec::new();
+        for name in &names {
+            if let Some(contents) = source.fetch(name)? {
+                releases.extend(
+                    IndexRelease::parse_file(&contents)
+                        .with_context(|| format!("reading the index file of {name}"))?,
+                );
             }
+        }
 
-            cdv
-        });
-        Ok(cdv.process_to_carriage())
+        let mut delta = IndexDelta::default();
+        self.add_indexed_crates(&releases, origin, &mut delta);
+        let mut next_id = self.next_release_id();
+        for release in &releases {
+            if self.apply_indexed_release(release, next_id, origin, registries, &mut delta) {
+                next_id += 1;
+            }
+        }
+
+        self.resolve_dependencies();
+        Ok(delta)
+    }
+
+    fn next_crate_id(map: &SichtMap<u32, String, Crate>) -> u32 {
+        map.iter().map(|(id, _)| *id).max().map_or(1, |id| id + 1)
+    }
+
+    fn next_release_id(&self) -> u32 {
+        self.lookup
+            .borrow()
+            .dependency_version
+            .keys()
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    /// The crate of `registry` going by `name`, resolved through the lookup so that names are
+    /// compared the way crates.io does.
+    pub fn crate_in_registry<'m>(
+        map: &'m SichtMap<u32, String, Crate>,
+        lookup: &Lookup,
+        registry: Option<&str>,
+        name: &str,
+    ) -> Option<&'m Crate> {
+        map.get_with_base_key(lookup.crate_in_registry(registry, name)?)
+    }
+
+    /// Keys `crates` by id and by `crate_key`.
+    pub fn index_crates(crates: Vec<Crate>) -> SichtMap<u32, String, Crate> {
+        crates
+            .into_iter()
+            .map(|krate| {
+                let key = crate_key(krate.krate.registry.as_deref(), &krate.krate.name);
+                (krate.krate.id, key, krate)
+            })
+            .collect()
     }
 
-    #[allow(clippy::needless_for_each)]
-    pub fn process_dependencies(
+    fn add_indexed_crates(
         &self,
-        dependencies: &BTreeMap<u32, Vec<u32>>,
-        crates_list: &BTreeMap<u32, u32>,
+        releases: &[IndexRelease],
+        origin: Option<&str>,
+        delta: &mut IndexDelta,
     ) {
-        dependencies.iter().for_each(|(version_id, crates)| {
-            crates
-                .iter()
-                .for_each(|ver| self.add_dependency_to_crate(*version_id, *ver, crates_list));
-        });
+        let mut map = self.map.borrow_mut();
+        let mut lookup = self.lookup.borrow_mut();
+        let mut next_id = Self::next_crate_id(&map);
+        for release in releases {
+            if Self::crate_in_registry(&map, &lookup, origin, &release.name).is_none() {
+                let mut kiste = Kiste::indexed(next_id, release.name.clone());
+                kiste.registry = origin.map(str::to_owned);
+                lookup.insert_krate(&kiste);
+                map.insert_with_both_keys(
+                    next_id,
+                    crate_key(origin, &release.name),
+                    Crate::new(kiste),
+                );
+                delta.crates += 1;
+                next_id += 1;
+            }
+        }
     }
 
-    pub fn add_dependency_to_crate(
+    /// Returns whether `id` was taken by a new release.
+    fn apply_indexed_release(
         &self,
-        version_id: u32,
-        crate_id: u32,
-        crates: &BTreeMap<u32, u32>,
+        indexed: &IndexRelease,
+        id: u32,
+        origin: Option<&str>,
+        registries: &[Registry],
+        delta: &mut IndexDelta,
+    ) -> bool {
+        let map = self.map.borrow();
+        let mut lookup = self.lookup.borrow_mut();
+        let Some(krate) = Self::crate_in_registry(&map, &lookup, origin, &indexed.name) else {
+            return false;
+        };
+
+        if let Some(mut release) = krate.release(&indexed.vers) {
+            if release.yanked != indexed.yanked {
+                if indexed.yanked {
+                    delta.yanked += 1;
+                } else {
+                    delta.unyanked += 1;
+                }
+                release.yanked = indexed.yanked;
+                krate.add_release(release);
+            }
+            return false;
+        }
+
+        let release = Release {
+            id,
+            num: indexed.vers.clone(),
+            yanked: indexed.yanked,
+            created_at: String::new(),
+            downloads: 0,
+            features: indexed.features(),
+            dependencies: SichtCell::default(),
+        };
+        let skids = indexed
+            .deps
+            .iter()
+            .filter_map(|dependency| {
+                // No `registry` means the one the index belongs to, registries that aren't
+                // configured can't be followed.
+                let registry = match &dependency.registry {
+                    None => origin,
+                    Some(url) => registry::resolve_url(registries, url)?,
+                };
+                let crate_id = *lookup.crate_in_registry(registry, dependency.crate_name())?;
+                Some(dependency.to_skid(crate_id))
+            })
+            .collect::<Vec<_>>();
+        for skid in skids {
+            lookup.insert_dependent(skid.dependency, id);
+            release.add_dependency(skid);
+        }
+        lookup.insert_dependency_relation(id, krate.krate.id);
+        krate.add_release(release);
+        delta.releases += 1;
+        true
+    }
+
+    /// Merges every configured registry into the graph, dumps by renumbering their crates and
+    /// releases past the ones already here, index checkouts through `apply_index`. The waybill
+    /// notes each source, so that a cache built with them notices when they change. A registry
+    /// can only depend on the ones listed before it.
+    pub fn merge_registries(&mut self, registries: &[Registry], strict: bool) -> Result<()> {
+        for registry in registries {
+            if !registry.source.exists() {
+                bail!(
+                    "the source of the {} registry, {}, doesn't exist",
+                    registry.name,
+                    registry.source.display()
+                );
+            }
+            let waybill = registry
+                .waybill()
+                .with_context(|| format!("reading the source of the {} registry", registry.name))?;
+            if registry.is_dump() {
+                let loading = || format!("loading the {} registry", registry.name);
+                let (other, mut tally) =
+                    Carriage::unarchive_tallied(&registry.source, false).with_context(loading)?;
+                self.merge_dump(&other, &registry.name, registries, &mut tally.dependencies);
+                tally.settle(strict).with_context(loading)?;
+                self.waybill.clean &= tally.is_clean();
+            } else {
+                let source = IndexSource::Checkout(registry.source.clone());
+                self.apply_index(&source, &[], Some(&registry.name), registries)
+                    .with_context(|| format!("loading the {} registry", registry.name))?;
+            }
+            self.waybill
+                .registries
+                .insert(registry.name.clone(), waybill);
+        }
+        self.resolve_dependencies();
+        Ok(())
+    }
+
+    /// Takes over what `other` has that `registry` doesn't have here yet: its new crates, which get
+    /// new ids, and the new releases of crates already here. A crate `other` lists from another
+    /// registry, by the index URL in its `registry` column, is looked up among the ones merged
+    /// before. Edges follow the crates where they ended up, those into a crate found nowhere are
+    /// counted as orphans.
+    fn merge_dump(
+        &self,
+        other: &Carriage,
+        registry: &str,
+        registries: &[Registry],
+        tally: &mut TableTally,
     ) {
-        let d_id = crates.get(&version_id).unwrap();
+        let release_offset = self.next_release_id();
+        let mut map = self.map.borrow_mut();
+        let mut lookup = self.lookup.borrow_mut();
+        let others = other.map.borrow();
+
+        // Old ids to new ones, for the crates taken over and the ones already here alike.
+        let mut next_id = Self::next_crate_id(&map);
+        let mut ids = BTreeMap::new();
+        let mut own = Vec::new();
+        for (id, krate) in others.iter() {
+            let name = canonical(&krate.krate.name);
+            if let Some(url) = &krate.krate.registry {
+                let elsewhere = registry::resolve_url(registries, url)
+                    .and_then(|origin| lookup.crate_in_registry(origin, &name));
+                if let Some(elsewhere) = elsewhere {
+                    ids.insert(*id, *elsewhere);
+                }
+                continue;
+            }
+
+            let known = lookup.crate_in_registry(Some(registry), &name).copied();
+            let new_id = known.unwrap_or_else(|| {
+                let mut kiste = krate.krate.clone();
+                kiste.id = next_id;
+                kiste.registry = Some(registry.to_owned());
+                lookup.insert_krate(&kiste);
+                map.insert_with_both_keys(
+                    next_id,
+                    crate_key(Some(registry), &kiste.name),
+                    Crate::new(kiste),
+                );
+                next_id += 1;
+                next_id - 1
+            });
+            ids.insert(*id, new_id);
+            own.push(krate);
+        }
+
+        for krate in own {
+            let Some(merged) = map.get_with_base_key(&ids[&krate.krate.id]) else {
+                continue;
+            };
+            let versions = krate.versions.borrow();
+            for (_, release) in versions.iter() {
+                if merged.release(&release.num).is_some() {
+                    continue;
+                }
+                let id = release.id + release_offset;
+                let dependencies = release
+                    .dependencies
+                    .borrow()
+                    .iter()
+                    .filter_map(|skid| match ids.get(&skid.dependency) {
+                        Some(dependency) => Some(Skid {
+                            dependency: *dependency,
+                            ..skid.clone()
+                        }),
+                        None => {
+                            tally.orphan(&format_args!(
+                                "a dependency of version {} points at crate {}, which no merged \
+                                 registry has",
+                                release.id, skid.dependency
+                            ));
+                            None
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                lookup.insert_dependency_relation(id, merged.krate.id);
+                for skid in &dependencies {
+                    lookup.insert_dependent(skid.dependency, id);
+                }
+                merged.add_release(Release {
+                    id,
+                    dependencies: SichtCell::new(dependencies),
+                    ..release.clone()
+                });
+            }
+        }
+    }
+
+    /// Adds the releases to their crates, `None` standing for a version that names no crate.
+    pub fn process_releases(&self, releases: Vec<(Option<u32>, Release)>, tally: &mut TableTally) {
         let map = self.map.borrow();
-        let krate = map.get_with_base_key(&crate_id).unwrap();
-        let dependency = map.get_with_base_key(d_id).unwrap();
-        krate.add_dependency(dependency);
-    }
-
-    pub fn search(&self, krate: &String) -> Option<UnrolledCrate> {
-        let krate_id = self.lookup.borrow();
-        let krate_id = krate_id.get_crate_id(krate)?;
-        let root = self.map.borrow().get(krate_id).cloned();
-        dbg!(&root);
-        root.map(|r| self.generate_from_crate(r))
-    }
-
-    pub fn generate_from_crate(&self, krate: Crate) -> UnrolledCrate {
-        UnrolledCrate {
-            crate_id: krate.krate.id,
-            name: krate.krate.name,
-            dependents: krate
-                .dependencies
-                .borrow()
-                .iter()
-                .filter_map(|(krate, _)| self.generate_if_not_traversed(*krate))
-                .collect(),
+        let mut lookup = self.lookup.borrow_mut();
+        for (crate_id, release) in releases {
+            let Some(crate_id) = crate_id else {
+                tally.orphan(&format_args!("version {} names no crate", release.id));
+                continue;
+            };
+            if let Some(krate) = map.get_with_base_key(&crate_id) {
+                lookup.insert_dependency_relation(release.id, crate_id);
+                krate.add_release(release);
+            } else {
+                tally.orphan(&format_args!(
+                    "version {} belongs to crate {crate_id}, which isn't in crates.csv",
+                    release.id
+                ));
+            }
+        }
+    }
+
+    /// Moves the edges, each next to the id of the release declaring it, into their releases.
+    /// The releases have to be in place already.
+    pub fn process_dependencies(&self, dependencies: Vec<(u32, Skid)>, tally: &mut TableTally) {
+        for (version_id, dependency) in dependencies {
+            if let Err(orphan) = self.add_dependency_to_crate(version_id, dependency) {
+                tally.orphan(&orphan);
+            }
         }
     }
 
-    pub fn generate_from_crate_name(&'a self, krate_name: &String) -> Option<UnrolledCrate> {
+    /// Adds the edge to its release, failing with the reason if either end isn't in the graph.
+    pub fn add_dependency_to_crate(&self, version_id: u32, dependency: Skid) -> Result<()> {
         let map = self.map.borrow();
-        let krate = map.get_with_outer_key(krate_name)?;
-        Some(UnrolledCrate {
-            crate_id: krate.krate.id,
-            name: krate_name.to_owned(),
-            dependents: krate
-                .dependencies
-                .borrow()
-                .iter()
-                .filter_map(|(krate, _)| self.generate_if_not_traversed(*krate))
-                .collect(),
-        })
-    }
-
-    pub fn generate_if_not_traversed(&self, crate_id: u32) -> Option<UnrolledCrate> {
-        let lookup = self.lookup.borrow();
-        let krate = lookup.get_crate_name(crate_id)?;
-        if self.traversed.borrow().contains_key(&crate_id) {
-            Some(UnrolledCrate {
-                crate_id: crate_id.to_owned(),
-                name: krate.to_owned(),
-                dependents: Vec::default(),
-            })
-        } else {
-            self.generate_from_crate_name(krate)
+        if map.get_with_base_key(&dependency.dependency).is_none() {
+            bail!(
+                "a dependency of version {version_id} points at crate {}, which isn't in crates.csv",
+                dependency.dependency
+            );
         }
+        let orphan =
+            || anyhow!("a dependency belongs to version {version_id}, which isn't in the graph");
+        let owner = self.lookup.borrow().dependency_version.get(&version_id).copied();
+        let krate = owner
+            .and_then(|crate_id| map.get_with_base_key(&crate_id))
+            .ok_or_else(orphan)?;
+        let versions = krate.versions.borrow();
+        let release = versions.get_with_base_key(&version_id).ok_or_else(orphan)?;
+        self.lookup
+            .borrow_mut()
+            .insert_dependent(dependency.dependency, version_id);
+        release.add_dependency(dependency);
+        Ok(())
+    }
+
+    pub fn resolve_dependencies(&self) {
+        let map = self.map.borrow();
+        let published = map
+            .iter()
+            .map(|(id, krate)| (*id, krate.published_versions()))
+            .collect::<BTreeMap<u32, Vec<(Version, String)>>>();
+
+        map.iter().for_each(|(_, krate)| {
+            krate.versions.borrow().iter().for_each(|(_, release)| {
+                release
+                    .dependencies
+                    .borrow_mut()
+                    .iter_mut()
+                    .for_each(|skid| {
+                        let versions = published.get(&skid.dependency).map(Vec::as_slice);
+                        skid.resolve(versions.unwrap_or_default());
+                    });
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{self, scratch};
+    use std::fs;
+
+    const CORP_CRATES: &str = "\
+created_at,description,homepage,id,max_features,max_upload_size,name,repository,updated_at
+2020-01-01 00:00:00,,,1,,,shared,,2024-01-01 00:00:00
+2020-01-01 00:00:00,,,2,,,app,,2024-01-01 00:00:00
+";
+
+    const CORP_VERSIONS: &str = "\
+bin_names,checksum,crate_id,crate_size,created_at,downloads,features,has_lib,id,license,links,num,published_by,rust_version,updated_at,yanked
+{},,1,,2024-01-01 00:00:00,10,{},t,10,MIT,,1.0.0,,,2024-01-01 00:00:00,f
+{},,2,,2024-01-01 00:00:00,10,{},t,20,MIT,,1.0.0,,,2024-01-01 00:00:00,f
+";
+
+    const CORP_DEPENDENCIES: &str = "\
+crate_id,default_features,explicit_name,features,id,kind,optional,req,target,version_id
+1,t,,{},100,0,f,^1,,20
+";
+
+    const CORP_INDEX: &str = r#"{"name":"corp_util","vers":"0.1.0","deps":[{"name":"serde","req":"^1","registry":"https://github.com/rust-lang/crates.io-index"},{"name":"secret","req":"^1","registry":"https://example.com/index"}],"features":{},"yanked":false}"#;
+
+    /// The header of `table` and its first `rows`.
+    fn head(table: &str, rows: usize) -> String {
+        table
+            .lines()
+            .take(rows + 1)
+            .flat_map(|line| [line, "\n"])
+            .collect()
+    }
+
+    fn crates_io(dir: &Path) -> Result<Carriage> {
+        let dump = dir.join("db-dump.tar.gz");
+        fixture::write_dump(&dump)?;
+        Carriage::unarchive(&dump, true)
+    }
+
+    fn dependencies_of(
+        carriage: &Carriage,
+        registry: &str,
+        name: &str,
+    ) -> Vec<(u32, Option<String>)> {
+        let map = carriage.map.borrow();
+        let lookup = carriage.lookup.borrow();
+        let krate = Carriage::crate_in_registry(&map, &lookup, Some(registry), name).unwrap();
+        let versions = krate.versions.borrow();
+        let (_, release) = versions.iter().next().unwrap();
+        release
+            .dependencies
+            .borrow()
+            .iter()
+            .map(|skid| (skid.dependency, skid.version.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn merged_dumps_point_edges_at_crates_already_here() -> Result<()> {
+        let dir = scratch("merge-dump");
+        let carriage = crates_io(&dir)?;
+        let earlier = dir.join("earlier.tar.gz");
+        let later = dir.join("later.tar.gz");
+        fixture::write_tables(
+            &earlier,
+            &head(CORP_CRATES, 1),
+            &head(CORP_VERSIONS, 1),
+            &head(CORP_DEPENDENCIES, 0),
+        )?;
+        fixture::write_tables(&later, CORP_CRATES, CORP_VERSIONS, CORP_DEPENDENCIES)?;
+
+        let mut tally = TableTally::new("dependencies.csv");
+        carriage.merge_dump(&Carriage::unarchive(&earlier, true)?, "corp", &[], &mut tally);
+        carriage.merge_dump(&Carriage::unarchive(&later, true)?, "corp", &[], &mut tally);
+        carriage.resolve_dependencies();
+
+        let lookup = carriage.lookup.borrow();
+        let shared = *lookup.crate_in_registry(Some("corp"), "shared").unwrap();
+        assert_eq!(shared, 3);
+        assert_eq!(lookup.crate_in_registry(Some("corp"), "app"), Some(&4));
+        drop(lookup);
+        assert_eq!(
+            dependencies_of(&carriage, "corp", "app"),
+            [(shared, Some("1.0.0".to_owned()))]
+        );
+        assert_eq!(carriage.map.borrow().iter().count(), 4);
+        assert!(tally.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn merged_dumps_add_releases_to_crates_already_here() -> Result<()> {
+        let dir = scratch("merge-releases");
+        let carriage = crates_io(&dir)?;
+        let earlier = dir.join("earlier.tar.gz");
+        let later = dir.join("later.tar.gz");
+        let versions = format!(
+            "{}{{}},,1,,2024-02-01 00:00:00,5,{{}},t,11,MIT,,1.1.0,,,2024-02-01 00:00:00,f\n",
+            head(CORP_VERSIONS, 1)
+        );
+        fixture::write_tables(&earlier, CORP_CRATES, CORP_VERSIONS, CORP_DEPENDENCIES)?;
+        fixture::write_tables(&later, CORP_CRATES, &versions, CORP_DEPENDENCIES)?;
+
+        let mut tally = TableTally::new("dependencies.csv");
+        carriage.merge_dump(&Carriage::unarchive(&earlier, true)?, "corp", &[], &mut tally);
+        carriage.merge_dump(&Carriage::unarchive(&later, true)?, "corp", &[], &mut tally);
+        carriage.resolve_dependencies();
+
+        let map = carriage.map.borrow();
+        let lookup = carriage.lookup.borrow();
+        let shared = Carriage::crate_in_registry(&map, &lookup, Some("corp"), "shared").unwrap();
+        let nums = shared
+            .published_versions()
+            .into_iter()
+            .map(|(_, num)| num)
+            .collect::<Vec<_>>();
+        assert_eq!(nums, ["1.1.0", "1.0.0"]);
+        drop((map, lookup));
+        assert_eq!(
+            dependencies_of(&carriage, "corp", "app"),
+            [(3, Some("1.1.0".to_owned()))]
+        );
+        assert!(tally.is_clean());
+        Ok(())
+    }
+
+    #[test]
+    fn merged_dumps_follow_edges_into_other_registries() -> Result<()> {
+        let dir = scratch("merge-across");
+        let carriage = crates_io(&dir)?;
+        let dump = dir.join("corp.tar.gz");
+        let crates = "\
+created_at,description,homepage,id,max_features,max_upload_size,name,repository,registry,updated_at
+2017-02-01 00:00:00,,,1,,,serde,,https://github.com/rust-lang/crates.io-index,2024-01-01 00:00:00
+2020-01-01 00:00:00,,,2,,,app,,,2024-01-01 00:00:00
+2020-01-01 00:00:00,,,3,,,secret,,https://example.com/index,2024-01-01 00:00:00
+";
+        let dependencies = format!("{}3,t,,{{}},101,0,f,^1,,20\n", head(CORP_DEPENDENCIES, 1));
+        fixture::write_tables(&dump, crates, CORP_VERSIONS, &dependencies)?;
+
+        let mut tally = TableTally::new("dependencies.csv");
+        carriage.merge_dump(&Carriage::unarchive(&dump, true)?, "corp", &[], &mut tally);
+        carriage.resolve_dependencies();
+
+        // The edge into crates.io is kept, the one into an unknown registry is an orphan.
+        assert_eq!(
+            dependencies_of(&carriage, "corp", "app"),
+            [(1, Some("1.0.200".to_owned()))]
+        );
+        assert_eq!(tally.orphaned, 1);
+        let lookup = carriage.lookup.borrow();
+        assert_eq!(lookup.crate_in_registry(Some("corp"), "serde"), None);
+        assert_eq!(lookup.crate_in_registry(Some("corp"), "secret"), None);
+        drop(lookup);
+        assert_eq!(carriage.map.borrow().iter().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn merged_indexes_follow_edges_into_configured_registries() -> Result<()> {
+        let dir = scratch("merge-index");
+        let mut carriage = crates_io(&dir)?;
+        let checkout = dir.join("corp");
+        let file = checkout.join(IndexSource::index_path("corp_util")?);
+        fs::create_dir_all(file.parent().unwrap())?;
+        fs::write(&file, CORP_INDEX)?;
+        let corp =
+            Registry::new("corp", checkout).with_index("https://corp.example/index".to_owned());
+
+        carriage.merge_registries(std::slice::from_ref(&corp), true)?;
+        let unchanged = corp.is_unchanged(&carriage.waybill.registries["corp"]);
+        let missing = Registry {
+            source: dir.join("gone"),
+            ..corp
+        };
+        let mut other = crates_io(&dir)?;
+        let gone = other.merge_registries(&[missing], true);
+
+        // The edge into crates.io is kept, the one into an unknown registry can't be followed.
+        assert_eq!(
+            dependencies_of(&carriage, "corp", "corp_util"),
+            [(1, Some("1.0.200".to_owned()))]
+        );
+        assert!(unchanged);
+        assert!(gone.is_err_and(|error| error.to_string().contains("doesn't exist")));
+        Ok(())
     }
 }
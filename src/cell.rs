@@ -1,5 +1,5 @@
 use kuh::Derow;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::{Ref, RefCell, RefMut};
 use std::fmt::{Debug, Formatter};
 use std::rc::Rc;
@@ -19,6 +19,14 @@ impl<T> SichtCell<T> {
     pub fn borrow_mut(&self) -> RefMut<'_, T> {
         self.0.borrow_mut()
     }
+
+    /// The value, moved out if this is the last handle to it and cloned otherwise.
+    pub fn into_inner(self) -> T
+    where
+        T: Clone,
+    {
+        Rc::unwrap_or_clone(self.0).into_inner()
+    }
 }
 
 impl<T> Debug for SichtCell<T>
@@ -52,3 +60,15 @@ where
         Ok(Self::new(map))
     }
 }
+
+impl<T> Serialize for SichtCell<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.borrow().serialize(serializer)
+    }
+}
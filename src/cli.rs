@@ -1,7 +1,9 @@
-use crate::download::{Config, Ignition};
-use crate::joystick::{Query, QueryAccumulator};
-use anyhow::Result;
-use clap::Parser;
+use crate::cabin::Cabin;
+use anyhow::{Result, anyhow};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use forklift::{Config, Engine, Format, Lager, Query};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -16,41 +18,138 @@ struct Args {
 
     #[arg(short, long)]
     fresh: bool,
+
+    /// Refuse a dump with rows that don't fit the expected schema, rather than skipping them.
+    #[arg(long)]
+    strict: bool,
+
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// The format the cache is written in. A cache already there is read in the format its
+    /// extension tells.
+    #[arg(long, value_enum)]
+    cache_format: Option<Lager>,
+
+    /// The crates.io database dump, `db-dump.tar.gz` in the cache directory by default, or in the
+    /// working directory if only that one has it.
+    #[arg(long)]
+    dump: Option<PathBuf>,
+
+    /// Where the graph is cached between runs, `$XDG_CACHE_HOME/forklift` by default.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Download the latest dump, then rebuild the cache from it.
+    Update {
+        /// Where to fetch the dump from, crates.io by default.
+        #[arg(long)]
+        url: Option<String>,
+
+        /// The expected SHA-256, instead of the one published next to the dump.
+        #[arg(long)]
+        sha256: Option<String>,
+    },
+    /// Apply new releases and yanks from a registry index to the cached graph.
+    Index {
+        /// A checkout of the index or the URL of a sparse mirror.
+        location: String,
+
+        /// Only refresh these crates. A mirror can't be listed, so it needs them.
+        crates: Vec<String>,
+    },
 }
 
 pub fn init() -> Result<()> {
     let args = Args::parse();
+    let mut config = Config::from_config_file()?
+        .with_fresh(args.fresh)
+        .with_strict(args.strict)
+        .with_progress(true);
+    if let Some(format) = args.format {
+        config = config.with_format(format);
+    }
+    if let Some(lager) = args.cache_format {
+        config = config.with_lager(lager);
+    }
+    if let Some(dump) = args.dump.clone() {
+        config = config.with_dump(dump);
+    }
+    if let Some(cache_dir) = args.cache_dir.clone() {
+        config = config.with_cache_dir(cache_dir);
+    }
     match args {
         Args {
-            package: Some(_),
+            command: Some(Command::Update { url, sha256 }),
+            ..
+        } => {
+            if let Some(url) = url {
+                config = config.with_dump_url(url);
+            }
+            Engine::update(config, sha256)?;
+            Ok(())
+        }
+        Args {
+            command: Some(Command::Index { location, crates }),
+            ..
+        } => {
+            let mut engine = Engine::load(config)?;
+            let delta = engine.apply_index(&location, &crates)?;
+            eprintln!("{delta}");
+            Ok(())
+        }
+        Args {
+            package: Some(manifest),
             interactive: false,
             query: None,
-            fresh,
-        } => todo!(),
+            ..
+        } => {
+            let engine = Engine::load(config)?;
+            let results = engine.run_manifest(&*manifest)?;
+            engine.process_output(Some(&results))
+        }
         Args {
             package: None,
             interactive: false,
             query: Some(q),
-            fresh: false,
+            ..
         } => {
-            let accumulator: Query = QueryAccumulator::from_input(&q).try_into()?;
-            let mut engine = Ignition::init(accumulator)?;
-            let results = engine.run()?;
-            engine.process_output()
+            let query = Query::parse(&q).map_err(|e| anyhow!(e.diagnose(&q)))?;
+            let engine = Engine::load(config)?;
+            let results = engine.query(&query);
+            engine.process_output(results.as_ref())
         }
         Args {
             package: None,
-            interactive: false,
-            query: Some(q),
-            fresh: true,
+            interactive: true,
+            query: None,
+            ..
         } => {
-            let accumulator: Query = QueryAccumulator::from_input(&q).try_into()?;
-            let mut engine = Ignition::init_with_config(accumulator, Config::fresh())?;
-            let results = engine.run()?;
-            dbg!(&results);
-            engine.process_output()
+            let engine = Engine::load(config)?;
+            Cabin::new(engine)?.run()
         }
-
-        _ => todo!("no query"),
+        Args {
+            package: None,
+            interactive: false,
+            query: None,
+            ..
+        } => Args::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "expected a --query, a --package, --interactive or a subcommand",
+            )
+            .exit(),
+        _ => Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--query, --package and --interactive can't be combined",
+            )
+            .exit(),
     }
 }
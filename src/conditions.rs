@@ -1,4 +1,9 @@
+use crate::horn::{QueryError, Span, suggest};
 use crate::joystick::{Panel, PanelValue};
+use crate::store::DependencyKind;
+use chrono::NaiveDate;
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
 use std::collections::VecDeque;
 
 #[derive(Debug, Clone)]
@@ -21,123 +26,691 @@ impl WhereClause {
         }
     }
 
-    pub fn from_parse_types(
-        sub_condition: ParseType,
-        operator: Option<ParseType>,
-        parameter: ParseType,
-    ) -> Option<Self> {
-        match (sub_condition, operator, parameter) {
-            (
-                ParseType::SubCondition(subc),
-                Some(ParseType::Operator(op)),
-                ParseType::Value(param),
-            ) => Some(Self::new(subc, Some(op), param)),
-            (ParseType::SubCondition(subc), None, ParseType::Value(param)) => {
-                Some(Self::new(subc, None, param))
+    /// Parses a single `<sub-condition> [operator] <value>` clause off the front of the stream.
+    /// `IN` takes a parenthesised, comma separated list and `BETWEEN` takes `<low> AND <high>`.
+    pub fn try_from_stream(stream: &mut TokenStream<'_>) -> Result<Self, QueryError> {
+        let token = stream.next("a condition")?;
+        let sub_condition = SubCondition::try_from_token(token)
+            .ok_or_else(|| stream.unexpected(token, "a condition", SubCondition::KEYWORDS))?;
+        let operator = stream.front().and_then(Operator::try_from_token);
+        if operator.is_some() {
+            let operator_token = stream.next("an operator")?;
+            if !sub_condition.supports(operator) {
+                return Err(QueryError::UnsupportedOperator {
+                    operator: operator_token.to_owned(),
+                    condition: token.to_owned(),
+                    span: stream.span(operator_token),
+                });
             }
-            _ => None,
         }
-    }
 
-    pub fn try_from_tokens(tokens: &Panel<'_>) -> Option<Self> {
-        let Panel::TokenValue(tokens) = tokens else {
-            return None;
+        let expected = sub_condition.expected();
+        let parse = |stream: &TokenStream<'_>, operator: Option<Operator>, token: &str| {
+            sub_condition
+                .parse_value(operator, token)
+                .ok_or_else(|| stream.invalid(token, expected))
         };
-
-        let (clause, stack, count) = tokens.iter().fold(
-            (<Option<WhereClause>>::None, VecDeque::new(), 0usize),
-            |(mut clause, mut stack, count), word| match (clause, count) {
-                (c, 0)
-                    if let Some(subc @ SubCondition::Version) =
-                        SubCondition::try_from_token(word) =>
-                {
-                    stack.push_back(ParseType::SubCondition(subc));
-                    (c, stack, count + 2)
-                }
-                (c, 2)
-                    if let constraint @ PanelValue::Semver(_) =
-                        PanelValue::try_from_token(word) =>
-                {
-                    stack.push_back(ParseType::Value(constraint));
-                    (c, stack, count + 1)
+        let parameter = match operator {
+            Some(Operator::In) => PanelValue::List(
+                take_list(stream)?
+                    .into_iter()
+                    .map(|token| parse(stream, Some(Operator::Equals), token))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(Operator::Between) => {
+                let low = stream.next(expected)?;
+                let and = stream.next("AND")?;
+                if Conjunction::try_from_token(and) != Some(Conjunction::And) {
+                    return Err(stream.unexpected(and, "AND", Conjunction::KEYWORDS));
                 }
-
-                (None, _) if word == &"and" => {
-                    todo!()
-                }
-                _ => todo!(),
-            },
-        );
-
-        let (subcondition, operator, value) = match (stack, count) {
-            (mut s, 3) => {
-                let subcondition = s.pop_front().unwrap();
-                let (operator, value) = if (s.len()) == 1 {
-                    (None, s.pop_front().unwrap())
-                } else {
-                    (s.pop_front(), s.pop_front().unwrap())
-                };
-                s.clear();
-                (subcondition, operator, value)
+                let high = stream.next(expected)?;
+                PanelValue::Range(
+                    Box::new(parse(stream, Some(Operator::GreaterEq), low)?),
+                    Box::new(parse(stream, Some(Operator::LessEq), high)?),
+                )
             }
-            (s, _) => {
-                todo!()
+            operator => {
+                let token = stream.next(expected)?;
+                parse(stream, operator, token)?
             }
         };
 
-        Self::from_parse_types(subcondition, operator, value)
+        Ok(Self::new(sub_condition, operator, parameter))
     }
-}
-#[derive(Debug, Clone)]
-pub enum ParseType {
-    SubCondition(SubCondition),
-    Operator(Operator),
-    Value(PanelValue),
-}
 
-impl ParseType {
-    pub fn from_tokens(_tokens: &[&str]) -> Self {
-        todo!()
+    /// `None` when the candidate doesn't carry what the clause looks at, e.g. the kind of the
+    /// root, which has no edge. A bound is the exception: a node without downloads or a creation
+    /// date, like a release only known from an index, isn't above `downloads > 1000`, so it
+    /// fails the clause rather than slipping through.
+    pub fn evaluate(&self, candidate: &Candidate<'_>) -> Option<bool> {
+        let operator = self.operator.unwrap_or(Operator::Equals);
+        let outcome = match self.sub_condition {
+            SubCondition::Version if candidate.unversioned => return None,
+            SubCondition::Version => candidate
+                .version
+                .map(|version| operator.matches_version(version, &self.parameter)),
+            _ => candidate
+                .panel_value(self.sub_condition)
+                .map(|subject| operator.matches(&subject, &self.parameter)),
+        };
+        outcome.or_else(|| operator.is_bound().then_some(false))
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubCondition {
     Version,
+    Name,
+    Downloads,
+    Created,
+    Kind,
+    Optional,
 }
 
 impl SubCondition {
+    pub const KEYWORDS: &[&'static str] = &[
+        "version",
+        "name",
+        "crate",
+        "downloads",
+        "created",
+        "kind",
+        "optional",
+    ];
+
     pub fn try_from_token(input: &str) -> Option<Self> {
         match input {
             "version" => Some(SubCondition::Version),
-            _ => todo!(),
+            "name" | "crate" => Some(SubCondition::Name),
+            "downloads" => Some(SubCondition::Downloads),
+            "created" => Some(SubCondition::Created),
+            "kind" => Some(SubCondition::Kind),
+            "optional" => Some(SubCondition::Optional),
+            _ => None,
+        }
+    }
+
+    pub fn supports(self, operator: Option<Operator>) -> bool {
+        matches!(
+            (self, operator),
+            (
+                _,
+                None | Some(Operator::Equals | Operator::NotEquals | Operator::In)
+            ) | (SubCondition::Name, Some(Operator::Like))
+                | (
+                    SubCondition::Version | SubCondition::Downloads | SubCondition::Created,
+                    Some(
+                        Operator::Less
+                            | Operator::LessEq
+                            | Operator::Greater
+                            | Operator::GreaterEq
+                            | Operator::Between,
+                    ),
+                )
+                | (
+                    SubCondition::Version,
+                    Some(Operator::Tilde | Operator::Caret)
+                )
+        )
+    }
+
+    /// What a value compared with this is expected to look like, for error messages.
+    pub fn expected(self) -> &'static str {
+        match self {
+            SubCondition::Version => "a version requirement",
+            SubCondition::Name => "a crate name",
+            SubCondition::Downloads => "a number",
+            SubCondition::Created => "a date like 2024-01-31",
+            SubCondition::Kind => "normal, build or dev",
+            SubCondition::Optional => "true or false",
+        }
+    }
+
+    /// Versions carry their operator inside the `VersionReq`, everything else is parsed as is.
+    pub fn parse_value(self, operator: Option<Operator>, token: &str) -> Option<PanelValue> {
+        match self {
+            SubCondition::Version => {
+                let prefix = operator.map_or("", Operator::semver_prefix);
+                VersionReq::parse(&format!("{prefix}{token}"))
+                    .ok()
+                    .map(PanelValue::Semver)
+            }
+            SubCondition::Name => Some(PanelValue::Crate(token.to_owned())),
+            SubCondition::Downloads => token.parse().ok().map(PanelValue::Number),
+            SubCondition::Created => token.parse().ok().map(PanelValue::Date),
+            SubCondition::Kind => DependencyKind::try_from_token(token).map(PanelValue::Kind),
+            SubCondition::Optional => token.parse().ok().map(PanelValue::Bool),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Equals,
+    NotEquals,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Tilde,
+    Caret,
+    In,
+    Like,
+    Between,
+}
+
+impl Operator {
+    pub fn try_from_token(input: &str) -> Option<Self> {
+        match input {
+            "=" | "==" => Some(Operator::Equals),
+            "!=" | "<>" => Some(Operator::NotEquals),
+            "<" => Some(Operator::Less),
+            "<=" => Some(Operator::LessEq),
+            ">" => Some(Operator::Greater),
+            ">=" => Some(Operator::GreaterEq),
+            "~" => Some(Operator::Tilde),
+            "^" => Some(Operator::Caret),
+            "in" | "IN" => Some(Operator::In),
+            "like" | "LIKE" => Some(Operator::Like),
+            "between" | "BETWEEN" => Some(Operator::Between),
+            _ => None,
+        }
+    }
+
+    /// Operators that only hold for a value on the right side of a bound.
+    pub fn is_bound(self) -> bool {
+        matches!(
+            self,
+            Operator::Less
+                | Operator::LessEq
+                | Operator::Greater
+                | Operator::GreaterEq
+                | Operator::Between
+                | Operator::Tilde
+                | Operator::Caret
+        )
+    }
+
+    pub fn semver_prefix(self) -> &'static str {
+        match self {
+            Operator::Equals | Operator::NotEquals => "=",
+            Operator::Less => "<",
+            Operator::LessEq => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEq => ">=",
+            Operator::Tilde => "~",
+            Operator::Caret => "^",
+            Operator::In | Operator::Like | Operator::Between => "",
+        }
+    }
+
+    pub fn matches_version(self, version: &Version, parameter: &PanelValue) -> bool {
+        match parameter {
+            PanelValue::Semver(req) => req.matches(version) != (self == Operator::NotEquals),
+            PanelValue::List(items) => {
+                let found = items
+                    .iter()
+                    .any(|item| Operator::Equals.matches_version(version, item));
+                found != (self == Operator::NotEquals)
+            }
+            PanelValue::Range(low, high) => {
+                Operator::Equals.matches_version(version, low)
+                    && Operator::Equals.matches_version(version, high)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn matches(self, subject: &PanelValue, parameter: &PanelValue) -> bool {
+        match (self, subject, parameter) {
+            (Operator::In, _, PanelValue::List(items)) => items
+                .iter()
+                .any(|item| subject.compare(item) == Some(Ordering::Equal)),
+            (Operator::Between, _, PanelValue::Range(low, high)) => {
+                subject.compare(low).is_some_and(Ordering::is_ge)
+                    && subject.compare(high).is_some_and(Ordering::is_le)
+            }
+            (Operator::Like, PanelValue::Crate(name), PanelValue::Crate(pattern)) => {
+                glob_matches(pattern, name)
+            }
+            (operator, _, parameter) => subject
+                .compare(parameter)
+                .is_some_and(|ordering| operator.accepts(ordering)),
+        }
+    }
+
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equals => ordering.is_eq(),
+            Operator::NotEquals => ordering.is_ne(),
+            Operator::Less => ordering.is_lt(),
+            Operator::LessEq => ordering.is_le(),
+            Operator::Greater => ordering.is_gt(),
+            Operator::GreaterEq => ordering.is_ge(),
+            _ => false,
+        }
+    }
+}
+
+/// A node of the tree as seen by a `WHERE` clause. Anything a clause can't see on the node is
+/// left empty, and clauses on it abstain instead of failing, bounds aside.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub version: Option<&'a Version>,
+    pub downloads: Option<u64>,
+    pub created: Option<NaiveDate>,
+    pub kind: Option<DependencyKind>,
+    pub optional: Option<bool>,
+    /// Version clauses abstain on the node even as bounds, they are about another node, e.g. the
+    /// root of a `REVERSE` tree below its direct dependents.
+    pub unversioned: bool,
 }
 
+impl<'a> Candidate<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_created(mut self, created: Option<NaiveDate>) -> Self {
+        self.created = created;
+        self
+    }
+
+    pub fn panel_value(&self, sub_condition: SubCondition) -> Option<PanelValue> {
+        match sub_condition {
+            SubCondition::Name => Some(PanelValue::Crate(self.name.to_owned())),
+            SubCondition::Downloads => self.downloads.map(PanelValue::Number),
+            SubCondition::Created => self.created.map(PanelValue::Date),
+            SubCondition::Kind => self.kind.map(PanelValue::Kind),
+            SubCondition::Optional => self.optional.map(PanelValue::Bool),
+            SubCondition::Version => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct PredicateComposition {
     left: Predicate,
     conjunction: Option<Conjunction>,
     right: Option<Predicate>,
 }
 
+impl PredicateComposition {
+    pub fn single(left: Predicate) -> Self {
+        Self {
+            left,
+            conjunction: None,
+            right: None,
+        }
+    }
+
+    pub fn join(self, conjunction: Conjunction, right: Predicate) -> Self {
+        Self {
+            left: self.into_predicate(),
+            conjunction: Some(conjunction),
+            right: Some(right),
+        }
+    }
+
+    pub fn into_predicate(self) -> Predicate {
+        match self {
+            Self {
+                left,
+                conjunction: None,
+                right: None,
+            } => left,
+            composition => Predicate::Group(Box::new(composition)),
+        }
+    }
+
+    /// Parses the tokens of a `WHERE` button. `AND` binds tighter than `OR`, and parentheses
+    /// group as usual. `keyword` is the `WHERE` itself and `input` the whole query, both only
+    /// serve to point errors at the right spot.
+    pub fn try_from_tokens<'a>(
+        tokens: &Panel<'a>,
+        keyword: &'a str,
+        input: &'a str,
+    ) -> Result<Self, QueryError> {
+        let Panel::TokenValue(tokens) = tokens else {
+            return Err(QueryError::MissingValue {
+                expected: "a condition",
+                after: keyword.to_owned(),
+                span: Span::after(input, keyword),
+            });
+        };
+
+        let mut stream = TokenStream::new(input, keyword, split_parentheses(tokens));
+        let composition = Self::parse_disjunction(&mut stream)?;
+        match stream.front() {
+            None => Ok(composition),
+            Some(token) => Err(stream.unexpected(
+                token,
+                "AND, OR or the end of the conditions",
+                Conjunction::KEYWORDS,
+            )),
+        }
+    }
+
+    fn parse_disjunction(stream: &mut TokenStream<'_>) -> Result<Self, QueryError> {
+        let mut composition = Self::parse_conjunction(stream)?;
+        while let Some(Conjunction::Or) = stream.front().and_then(Conjunction::try_from_token) {
+            stream.pop_front();
+            let right = Self::parse_conjunction(stream)?;
+            composition = composition.join(Conjunction::Or, right.into_predicate());
+        }
+
+        Ok(composition)
+    }
+
+    fn parse_conjunction(stream: &mut TokenStream<'_>) -> Result<Self, QueryError> {
+        let mut composition = Self::single(Predicate::try_from_stream(stream)?);
+        while let Some(Conjunction::And) = stream.front().and_then(Conjunction::try_from_token) {
+            stream.pop_front();
+            let right = Predicate::try_from_stream(stream)?;
+            composition = composition.join(Conjunction::And, right);
+        }
+
+        Ok(composition)
+    }
+
+    /// Three-valued evaluation: `None` means none of the clauses applied to the candidate.
+    pub fn evaluate(&self, candidate: &Candidate<'_>) -> Option<bool> {
+        let left = self.left.evaluate(candidate);
+        let (Some(conjunction), Some(right)) = (&self.conjunction, &self.right) else {
+            return left;
+        };
+
+        match (conjunction, left, right.evaluate(candidate)) {
+            (Conjunction::And, Some(false), _) | (Conjunction::And, _, Some(false)) => Some(false),
+            (Conjunction::Or, Some(true), _) | (Conjunction::Or, _, Some(true)) => Some(true),
+            (_, Some(outcome), _) | (_, None, Some(outcome)) => Some(outcome),
+            (_, None, None) => None,
+        }
+    }
+
+    pub fn admits(&self, candidate: &Candidate<'_>) -> bool {
+        self.evaluate(candidate) != Some(false)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Predicate {
-    Group(Box<Self>),
+    Group(Box<PredicateComposition>),
     Single(WhereClause),
 }
 
+impl Predicate {
+    pub fn try_from_stream(stream: &mut TokenStream<'_>) -> Result<Self, QueryError> {
+        if stream.front() == Some("(") {
+            stream.pop_front();
+            let group = PredicateComposition::parse_disjunction(stream)?;
+            match stream.next("`)`")? {
+                ")" => Ok(group.into_predicate()),
+                token => Err(stream.unexpected(token, "`)`", &[])),
+            }
+        } else {
+            WhereClause::try_from_stream(stream).map(Predicate::Single)
+        }
+    }
+
+    pub fn evaluate(&self, candidate: &Candidate<'_>) -> Option<bool> {
+        match self {
+            Predicate::Group(composition) => composition.evaluate(candidate),
+            Predicate::Single(clause) => clause.evaluate(candidate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Conjunction {
     And,
     Or,
 }
 
 impl Conjunction {
-    pub fn is_valid(conjunction: &str) -> bool {
-        conjunction == "and" || conjunction == "AND" || conjunction == "or" || conjunction == "OR"
+    pub const KEYWORDS: &[&'static str] = &["AND", "OR"];
+
+    pub fn try_from_token(input: &str) -> Option<Self> {
+        match input {
+            "and" | "AND" => Some(Conjunction::And),
+            "or" | "OR" => Some(Conjunction::Or),
+            _ => None,
+        }
+    }
+}
+
+/// The tokens of a `WHERE` button, remembering the last one taken so a missing part can be
+/// reported right behind it.
+pub struct TokenStream<'a> {
+    input: &'a str,
+    tokens: VecDeque<&'a str>,
+    last: &'a str,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(input: &'a str, keyword: &'a str, tokens: VecDeque<&'a str>) -> Self {
+        Self {
+            input,
+            tokens,
+            last: keyword,
+        }
+    }
+
+    pub fn front(&self) -> Option<&'a str> {
+        self.tokens.front().copied()
+    }
+
+    pub fn pop_front(&mut self) -> Option<&'a str> {
+        let token = self.tokens.pop_front()?;
+        self.last = token;
+        Some(token)
+    }
+
+    /// The next token, which has to be there.
+    pub fn next(&mut self, expected: &'static str) -> Result<&'a str, QueryError> {
+        self.pop_front().ok_or_else(|| QueryError::MissingValue {
+            expected,
+            after: self.last.to_owned(),
+            span: Span::after(self.input, self.last),
+        })
+    }
+
+    pub fn span(&self, token: &str) -> Span {
+        Span::of(self.input, token)
+    }
+
+    pub fn unexpected(
+        &self,
+        token: &str,
+        expected: &'static str,
+        candidates: &[&'static str],
+    ) -> QueryError {
+        QueryError::UnexpectedToken {
+            expected,
+            found: token.to_owned(),
+            span: self.span(token),
+            suggestion: suggest(token, candidates),
+        }
+    }
+
+    pub fn invalid(&self, token: &str, expected: &'static str) -> QueryError {
+        QueryError::InvalidValue {
+            expected,
+            found: token.to_owned(),
+            span: self.span(token),
+        }
+    }
+}
+
+/// The query is split on whitespace, so parentheses can still be glued to their neighbours as in
+/// `(kind` or `false)`. The pieces stay slices of the query to keep their position.
+fn split_parentheses<'a>(tokens: &[&'a str]) -> VecDeque<&'a str> {
+    tokens.iter().fold(VecDeque::new(), |mut stream, token| {
+        let mut rest = *token;
+        while rest.starts_with('(') {
+            stream.push_back(&rest[..1]);
+            rest = &rest[1..];
+        }
+
+        let trimmed = rest.trim_end_matches(')');
+        let closing = &rest[trimmed.len()..];
+        if !trimmed.is_empty() {
+            stream.push_back(trimmed);
+        }
+        stream.extend((0..closing.len()).map(|i| &closing[i..=i]));
+        stream
+    })
+}
+
+/// Collects the items of `( a, b, c )`, however the commas and parentheses were spaced.
+fn take_list<'a>(stream: &mut TokenStream<'a>) -> Result<Vec<&'a str>, QueryError> {
+    let opening = stream.next("a list like `(a, b)`")?;
+    if opening != "(" {
+        return Err(stream.unexpected(opening, "a list like `(a, b)`", &[]));
+    }
+
+    let mut items = Vec::new();
+    loop {
+        match stream.next("`)`")? {
+            ")" => break,
+            token => items.extend(token.split(',').filter(|item| !item.is_empty())),
+        }
+    }
+
+    if items.is_empty() {
+        return Err(QueryError::MissingValue {
+            expected: "at least one item",
+            after: opening.to_owned(),
+            span: stream.span(opening),
+        });
+    }
+    Ok(items)
+}
+
+/// `*` matches any run of characters and `?` matches exactly one.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(input: &str) -> PredicateComposition {
+        let tokens = Panel::TokenValue(input.split_whitespace().collect());
+        PredicateComposition::try_from_tokens(&tokens, &input[..0], input).unwrap()
+    }
+
+    #[test]
+    fn bounds_leave_out_nodes_without_the_data() {
+        let unknown = Candidate::new("serde");
+        let known = Candidate {
+            downloads: Some(5000),
+            created: NaiveDate::from_ymd_opt(2024, 1, 1),
+            ..Candidate::new("serde")
+        };
+
+        for input in [
+            "downloads > 1000",
+            "created >= 2020-01-01",
+            "downloads BETWEEN 1000 AND 10000",
+        ] {
+            assert!(!conditions(input).admits(&unknown), "{input}");
+            assert!(conditions(input).admits(&known), "{input}");
+        }
+        // A clause that doesn't apply leaves the outcome to the other side.
+        assert!(conditions("kind = normal").admits(&unknown));
+        assert!(!conditions("kind = normal OR downloads > 1000").admits(&unknown));
+        assert!(conditions("kind = normal OR downloads > 1000").admits(&known));
+    }
+
+    #[test]
+    fn operators_evaluate_against_the_candidate() {
+        let version = Version::new(1, 2, 3);
+        let candidate = Candidate {
+            version: Some(&version),
+            downloads: Some(5000),
+            created: NaiveDate::from_ymd_opt(2024, 1, 15),
+            kind: Some(DependencyKind::Build),
+            optional: Some(true),
+            ..Candidate::new("serde_json")
+        };
+
+        for (input, expected) in [
+            ("version != 1.2.3", false),
+            ("version != 1.0.0", true),
+            ("version IN (1.0.0, 1.2.3)", true),
+            ("version IN (1.0.0,2.0.0)", false),
+            ("version ~ 1.2", true),
+            ("version ~ 1.1", false),
+            ("version ^ 1.0", true),
+            ("version ^ 2", false),
+            ("version BETWEEN 1.0.0 AND 1.2.3", true),
+            ("version BETWEEN 1.2.3 AND 2.0.0", true),
+            ("version BETWEEN 1.2.4 AND 2.0.0", false),
+            ("name != serde", true),
+            ("name IN (serde, serde_json)", true),
+            ("name LIKE serde*", true),
+            ("name LIKE serde*json", true),
+            ("name LIKE s*e*n", true),
+            ("name LIKE serde?json", true),
+            ("name LIKE ?erde_json", true),
+            ("name LIKE serde_json*", true),
+            ("name LIKE *json", true),
+            ("name LIKE serde*yaml", false),
+            ("name LIKE serde_json?", false),
+            ("name LIKE serde", false),
+            ("created != 2024-01-15", false),
+            ("created IN (2024-01-15)", true),
+            ("created BETWEEN 2024-01-15 AND 2024-02-01", true),
+            ("created BETWEEN 2023-01-01 AND 2024-01-15", true),
+            ("created BETWEEN 2024-01-16 AND 2024-12-31", false),
+            ("downloads BETWEEN 5000 AND 5000", true),
+            ("kind = build", true),
+            ("kind != build", false),
+            ("kind IN (normal, dev)", false),
+            ("kind IN (build,dev)", true),
+            ("optional = true", true),
+        ] {
+            assert_eq!(conditions(input).evaluate(&candidate), Some(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn kinds_are_parsed_with_the_query() {
+        let input = "kind = build";
+        let mut stream = TokenStream::new(input, &input[..0], input.split_whitespace().collect());
+        let clause = WhereClause::try_from_stream(&mut stream).unwrap();
+        assert!(matches!(clause.parameter, PanelValue::Kind(DependencyKind::Build)));
     }
 }
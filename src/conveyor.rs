@@ -0,0 +1,329 @@
+use crate::lookup::crate_key;
+use crate::store::{Cdv, Crate, Depencil, Kiste, Lesart, Release, Skid};
+use crate::tally::{TableTally, Tally};
+use crate::waybill::DumpMetadata;
+use anyhow::{Result, anyhow};
+use csv::Reader;
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use sicht::SichtMap;
+use std::fs::File;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, ScopedJoinHandle};
+use std::time::{Duration, Instant};
+use tar::Archive;
+
+const CHUNK: usize = 1 << 16;
+/// How many chunks a table may run ahead of its parser before decompression waits.
+const BACKLOG: usize = 64;
+
+/// Carries the dump from the tarball into a `Cdv`. The archive is decompressed on the calling
+/// thread and each CSV table is streamed in chunks to a parser thread of its own. The tables
+/// come out of the one decoder one after another, so a table is parsed while it and the rest
+/// of the archive are being decompressed rather than side by side with the other tables. No
+/// table is held as text and each parser turns its rows into what the graph keeps of them as
+/// they come in, but the edges can only be attached once every release is known, so they wait
+/// in a list until the graph is built.
+pub struct Conveyor {
+    crates: Option<SyncSender<Vec<u8>>>,
+    dependencies: Option<SyncSender<Vec<u8>>>,
+    versions: Option<SyncSender<Vec<u8>>>,
+}
+
+impl Conveyor {
+    /// Loads the dump at `path`, drawing its progress on stderr if `progress`.
+    pub fn unload(path: &Path, progress: bool) -> Result<Cdv> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Self::unload_from(file, size, progress)
+    }
+
+    /// Loads the gzipped tarball `archive`, `size` bytes of it.
+    fn unload_from<R: Read>(archive: R, size: u64, progress: bool) -> Result<Cdv> {
+        let odometer = &Odometer::new(size);
+        thread::scope(|scope| {
+            let (crates, crates_belt) = Belt::new();
+            let (dependencies, dependencies_belt) = Belt::new();
+            let (versions, versions_belt) = Belt::new();
+            let conveyor = Conveyor {
+                crates: Some(crates),
+                dependencies: Some(dependencies),
+                versions: Some(versions),
+            };
+
+            let crates = scope.spawn(move || {
+                let mut tally = TableTally::new("crates.csv");
+                let crates =
+                    Self::rows::<Kiste>(crates_belt, odometer, &mut tally).collect::<Vec<_>>();
+                (crates, tally)
+            });
+            let dependencies = scope.spawn(move || {
+                let mut tally = TableTally::new("dependencies.csv");
+                let dependencies = Self::rows::<Depencil>(dependencies_belt, odometer, &mut tally)
+                    .map(|depencil| (depencil.version_id, Skid::from(&depencil)))
+                    .collect::<Vec<_>>();
+                (dependencies, tally)
+            });
+            let versions = scope.spawn(move || {
+                let mut tally = TableTally::new("versions.csv");
+                let releases = Self::rows::<Lesart>(versions_belt, odometer, &mut tally)
+                    .map(|lesart| (lesart.crate_id, Release::from(lesart)))
+                    .collect::<Vec<_>>();
+                (releases, tally)
+            });
+            let reporter = progress.then(|| scope.spawn(|| odometer.report()));
+
+            let fed = conveyor.feed(archive, odometer);
+            let crates = Self::join(crates, "crates.csv");
+            let dependencies = Self::join(dependencies, "dependencies.csv");
+            let versions = Self::join(versions, "versions.csv");
+            odometer.stop();
+            if let Some(reporter) = reporter {
+                let _ = reporter.join();
+            }
+
+            let (crates, crates_tally) = crates?;
+            let (dependencies, dependencies_tally) = dependencies?;
+            let (releases, versions_tally) = versions?;
+            Ok(Cdv {
+                crates: crates
+                    .into_iter()
+                    .map(|cr| (cr.id, crate_key(None, &cr.name), Crate::new(cr)))
+                    .collect::<SichtMap<u32, String, Crate>>(),
+                dependencies,
+                releases,
+                dumped_at: fed?,
+                tally: Tally {
+                    crates: crates_tally,
+                    versions: versions_tally,
+                    dependencies: dependencies_tally,
+                },
+            })
+        })
+    }
+
+    /// Decompresses the archive, handing the tables to their belts and returning the time the
+    /// dump was taken. A belt is closed as soon as its table is through, which is what lets the
+    /// parser finish.
+    fn feed<R: Read>(mut self, archive: R, odometer: &Odometer) -> Result<Option<String>> {
+        let metered = Metered {
+            inner: archive,
+            odometer,
+        };
+        let mut archive = Archive::new(GzDecoder::new(metered));
+        let mut dumped_at = None;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let belt = if path.ends_with("crates.csv") {
+                self.crates.take()
+            } else if path.ends_with("dependencies.csv") {
+                self.dependencies.take()
+            } else if path.ends_with("versions.csv") {
+                self.versions.take()
+            } else {
+                if path.ends_with("metadata.json") {
+                    dumped_at = serde_json::from_reader::<_, DumpMetadata>(entry)
+                        .ok()
+                        .map(|metadata| metadata.timestamp);
+                }
+                continue;
+            };
+            let Some(belt) = belt else {
+                continue;
+            };
+
+            loop {
+                let mut chunk = vec![0; CHUNK];
+                let read = entry.read(&mut chunk)?;
+                chunk.truncate(read);
+                // A parser that hung up has what it needs.
+                if read == 0 || belt.send(chunk).is_err() {
+                    break;
+                }
+            }
+        }
+
+        Ok(dumped_at)
+    }
+
+    /// The rows of a table that deserialize, the others are skipped and tallied.
+    fn rows<T: DeserializeOwned>(
+        belt: Belt,
+        odometer: &Odometer,
+        tally: &mut TableTally,
+    ) -> impl Iterator<Item = T> {
+        Reader::from_reader(belt)
+            .into_deserialize::<T>()
+            .filter_map(move |row| {
+                odometer.rows.fetch_add(1, Ordering::Relaxed);
+                tally.read += 1;
+                row.map_err(|error| tally.skip(&error)).ok()
+            })
+    }
+
+    fn join<T>(handle: ScopedJoinHandle<'_, T>, table: &str) -> Result<T> {
+        handle
+            .join()
+            .map_err(|_| anyhow!("parsing {table} panicked"))
+    }
+}
+
+/// The receiving end of a table, read by its parser as if it were the file.
+struct Belt {
+    chunks: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    at: usize,
+}
+
+impl Belt {
+    fn new() -> (SyncSender<Vec<u8>>, Self) {
+        let (sender, chunks) = mpsc::sync_channel(BACKLOG);
+        let belt = Self {
+            chunks,
+            chunk: Vec::new(),
+            at: 0,
+        };
+        (sender, belt)
+    }
+}
+
+impl Read for Belt {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.at == self.chunk.len() {
+            let Ok(chunk) = self.chunks.recv() else {
+                return Ok(0);
+            };
+            self.chunk = chunk;
+            self.at = 0;
+        }
+
+        let read = buf.len().min(self.chunk.len() - self.at);
+        buf[..read].copy_from_slice(&self.chunk[self.at..self.at + read]);
+        self.at += read;
+        Ok(read)
+    }
+}
+
+/// Counts the compressed bytes as they are read.
+struct Metered<'o, R> {
+    inner: R,
+    odometer: &'o Odometer,
+}
+
+impl<R: Read> Read for Metered<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.odometer
+            .bytes
+            .fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+/// Keeps track of how far the conveyor got. Asked to report, it redraws the figures on stderr
+/// while the dump loads if that's a terminal, and prints a summary at the end either way.
+struct Odometer {
+    started: Instant,
+    size: u64,
+    bytes: AtomicU64,
+    rows: AtomicU64,
+    done: AtomicBool,
+}
+
+impl Odometer {
+    const TICK: Duration = Duration::from_millis(250);
+
+    fn new(size: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            size,
+            bytes: AtomicU64::default(),
+            rows: AtomicU64::default(),
+            done: AtomicBool::default(),
+        }
+    }
+
+    fn stop(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    fn report(&self) {
+        let live = io::stderr().is_terminal();
+        while !self.done.load(Ordering::Relaxed) {
+            thread::sleep(Self::TICK);
+            if live {
+                eprint!("\r\x1b[Kloading the dump: {}", self.describe());
+            }
+        }
+        if live {
+            eprint!("\r\x1b[K");
+        }
+        eprintln!(
+            "loaded the dump in {:.1}s: {}",
+            self.started.elapsed().as_secs_f64(),
+            self.describe()
+        );
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn describe(&self) -> String {
+        const MIB: f64 = (1 << 20) as f64;
+        let rows = self.rows.load(Ordering::Relaxed);
+        let rate = rows as f64 / self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        format!(
+            "{:.1} of {:.1} MiB read, {rows} rows at {rate:.0} rows/s",
+            self.bytes.load(Ordering::Relaxed) as f64 / MIB,
+            self.size as f64 / MIB,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{self, CRATES, DEPENDENCIES, VERSIONS};
+
+    #[test]
+    fn belts_read_across_chunks() -> Result<()> {
+        let (sender, mut belt) = Belt::new();
+        for chunk in ["crate", "", "s.c", "sv"] {
+            sender.send(chunk.as_bytes().to_vec())?;
+        }
+        drop(sender);
+
+        let mut read = String::new();
+        belt.read_to_string(&mut read)?;
+        assert_eq!(read, "crates.csv");
+        Ok(())
+    }
+
+    #[test]
+    fn conveyor_unloads_every_table() -> Result<()> {
+        let tarball = fixture::tarball(CRATES, VERSIONS, DEPENDENCIES)?;
+        let size = tarball.len() as u64;
+        let cdv = Conveyor::unload_from(tarball.as_slice(), size, false)?;
+
+        assert_eq!(cdv.crates.iter().count(), 2);
+        assert_eq!(
+            cdv.releases
+                .iter()
+                .map(|(crate_id, release)| (release.id, *crate_id))
+                .collect::<Vec<_>>(),
+            [(10, Some(1)), (20, Some(2)), (21, Some(2))]
+        );
+        assert_eq!(
+            cdv.dependencies
+                .iter()
+                .map(|(version_id, skid)| (*version_id, skid.dependency))
+                .collect::<Vec<_>>(),
+            [(10, 2)]
+        );
+        assert!(cdv.tally.is_clean());
+        assert_eq!(cdv.tally.tables().map(|table| table.read), [2, 3, 1]);
+        Ok(())
+    }
+}
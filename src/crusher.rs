@@ -1,8 +1,10 @@
-use crate::carriage::Carriage;
+use crate::bale::Bale;
 use crate::fs::Mast;
 use crate::serproxy::CarriageSer;
 use anyhow::Error;
 use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
 use std::fs::OpenOptions;
 use std::path::Path;
 
@@ -30,3 +32,51 @@ impl Crusher for Mast {
         (ron::de::from_bytes(&contents)).map_err(Error::msg)
     }
 }
+
+/// The format the graph is cached in between runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lager {
+    Ron,
+    #[default]
+    Bale,
+}
+
+impl Lager {
+    /// The format of the cache at `path`, told by its extension.
+    #[must_use]
+    pub fn of_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "fork" | "ron" => Some(Lager::Ron),
+            "bale" => Some(Lager::Bale),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Lager::Ron => "lager.fork",
+            Lager::Bale => "lager.bale",
+        }
+    }
+
+    pub(crate) fn crush<P: AsRef<Path>>(
+        self,
+        mast: &Mast,
+        file: P,
+        contents: &CarriageSer,
+    ) -> Result<()> {
+        match self {
+            Lager::Ron => mast.crush(file, contents),
+            Lager::Bale => Bale.crush(file, contents),
+        }
+    }
+
+    pub(crate) fn uncrush(self, contents: Vec<u8>) -> Result<CarriageSer> {
+        match self {
+            Lager::Ron => Mast::uncrush(contents),
+            Lager::Bale => Bale::uncrush(contents),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use crate::store::{DependencyKind, Marker, UnrolledCrate};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Tree,
+    Json,
+}
+
+impl Format {
+    pub(crate) fn render<W: Write>(
+        self,
+        unrolled: Option<&UnrolledCrate>,
+        writer: &mut W,
+    ) -> Result<()> {
+        match (self, unrolled) {
+            (Format::Tree, Some(root)) => Dashboard::new(writer).render(root),
+            (Format::Tree, None) => {
+                writeln!(writer, "no crate matched the query")?;
+                Ok(())
+            }
+            (Format::Json, unrolled) => {
+                serde_json::to_writer_pretty(&mut *writer, &unrolled)?;
+                writeln!(writer)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Prints an `UnrolledCrate` the way `cargo tree` does: crates unrolled earlier in the tree are
+/// marked with `(*)` and edges back onto an ancestor with `(cycle)`.
+pub struct Dashboard<'w, W> {
+    writer: &'w mut W,
+    prefix: String,
+}
+
+impl<'w, W: Write> Dashboard<'w, W> {
+    pub fn new(writer: &'w mut W) -> Self {
+        Self {
+            writer,
+            prefix: String::new(),
+        }
+    }
+
+    pub fn render(mut self, root: &UnrolledCrate) -> Result<()> {
+        self.render_node(root)
+    }
+
+    fn render_node(&mut self, node: &UnrolledCrate) -> Result<()> {
+        Self::write_label(self.writer, node)?;
+        match node.marker {
+            Some(Marker::Repeated) => writeln!(self.writer, " (*)")?,
+            Some(Marker::Cycle) => writeln!(self.writer, " (cycle)")?,
+            None => writeln!(self.writer)?,
+        }
+
+        if node.dependents.is_empty() {
+            return Ok(());
+        }
+
+        let last = node.dependents.len() - 1;
+        node.dependents
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, dependent)| self.render_branch(dependent, i == last))
+    }
+
+    fn write_label(writer: &mut W, node: &UnrolledCrate) -> Result<()> {
+        match (&node.version, &node.req) {
+            (Some(version), _) => write!(writer, "{} v{version}", node.name)?,
+            (None, Some(req)) => write!(writer, "{} {req}", node.name)?,
+            (None, None) => write!(writer, "{}", node.name)?,
+        }
+        if let Some(kind @ (DependencyKind::Build | DependencyKind::Dev)) = node.kind {
+            write!(writer, " ({})", kind.as_str())?;
+        }
+        if let Some(registry) = &node.registry {
+            write!(writer, " (registry `{registry}`)")?;
+        }
+        Ok(())
+    }
+
+    fn render_branch(&mut self, node: &UnrolledCrate, last: bool) -> Result<()> {
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        write!(self.writer, "{}{branch}", self.prefix)?;
+        self.prefix.push_str(indent);
+        let rendered = self.render_node(node);
+        self.prefix.truncate(self.prefix.len() - indent.len());
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn node(name: &str, kind: DependencyKind, dependents: Vec<UnrolledCrate>) -> UnrolledCrate {
+        UnrolledCrate {
+            req: Some("^1".to_owned()),
+            kind: Some(kind),
+            ..UnrolledCrate::new(0, name.to_owned(), Some("1.0.0".to_owned()), dependents)
+        }
+    }
+
+    fn marked(node: UnrolledCrate, marker: Marker) -> UnrolledCrate {
+        UnrolledCrate {
+            marker: Some(marker),
+            ..node
+        }
+    }
+
+    fn internal(node: UnrolledCrate) -> UnrolledCrate {
+        UnrolledCrate {
+            registry: Some("internal".to_owned()),
+            ..node
+        }
+    }
+
+    fn tree() -> UnrolledCrate {
+        let ryu = UnrolledCrate {
+            version: None,
+            ..node("ryu", DependencyKind::Normal, Vec::default())
+        };
+        let itoa = internal(node("itoa", DependencyKind::Normal, vec![ryu]));
+        let repeated = marked(
+            internal(node("itoa", DependencyKind::Normal, Vec::default())),
+            Marker::Repeated,
+        );
+        let cycle = marked(
+            node("serde", DependencyKind::Normal, Vec::default()),
+            Marker::Cycle,
+        );
+        UnrolledCrate::new(
+            1,
+            "serde".to_owned(),
+            Some("1.0.0".to_owned()),
+            vec![
+                node("serde_derive", DependencyKind::Build, vec![cycle]),
+                itoa,
+                node("trybuild", DependencyKind::Dev, vec![repeated]),
+            ],
+        )
+    }
+
+    fn render(format: Format, unrolled: Option<&UnrolledCrate>) -> Result<String> {
+        let mut written = Vec::new();
+        format.render(unrolled, &mut written)?;
+        Ok(String::from_utf8(written)?)
+    }
+
+    #[test]
+    fn tree_draws_branches_and_markers() -> Result<()> {
+        assert_eq!(
+            render(Format::Tree, Some(&tree()))?,
+            "\
+serde v1.0.0
+├── serde_derive v1.0.0 (build)
+│   └── serde v1.0.0 (cycle)
+├── itoa v1.0.0 (registry `internal`)
+│   └── ryu ^1
+└── trybuild v1.0.0 (dev)
+    └── itoa v1.0.0 (registry `internal`) (*)
+"
+        );
+        assert_eq!(render(Format::Tree, None)?, "no crate matched the query\n");
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trips() -> Result<()> {
+        let unrolled = tree();
+        let rendered = serde_json::from_str::<Value>(&render(Format::Json, Some(&unrolled))?)?;
+
+        assert_eq!(rendered, serde_json::to_value(&unrolled)?);
+        assert_eq!(
+            rendered["dependents"][2],
+            json!({
+                "crate_id": 0,
+                "name": "trybuild",
+                "version": "1.0.0",
+                "req": "^1",
+                "kind": "dev",
+                "dependents": [{
+                    "crate_id": 0,
+                    "name": "itoa",
+                    "registry": "internal",
+                    "version": "1.0.0",
+                    "req": "^1",
+                    "kind": "normal",
+                    "marker": "repeated",
+                    "dependents": [],
+                }],
+            })
+        );
+        assert_eq!(render(Format::Json, None)?, "null\n");
+        Ok(())
+    }
+}
@@ -1,52 +1,197 @@
 use crate::carriage::Carriage;
+use crate::crusher::Lager;
+use crate::dashboard::Format;
 use crate::fs::Mast;
+use crate::index::{IndexDelta, IndexSource};
 use crate::joystick::Query;
+use crate::manifest::Workspace;
+use crate::rack::{Rack, Route};
+use crate::registry::Registry;
+use crate::serproxy::CarriageSer;
 use crate::store::UnrolledCrate;
-use anyhow::Result;
+use crate::waybill::Waybill;
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
 
-pub struct Ignition {
-    query: Query,
-    config: Config,
-}
+pub const DUMP_URL: &str = "https://static.crates.io/db-dump.tar.gz";
+const DUMP_FILE: &str = "db-dump.tar.gz";
+
+/// Loads the graph a `Config` points at, from the cache or else the dump.
+pub struct Ignition;
 
+/// Holds the frozen graph, so an `Engine` can be shared between threads and queried from all of
+/// them at once.
 pub struct Engine {
-    query: Query,
-    carriage: Carriage,
+    rack: Rack,
+    config: Config,
 }
 
 impl Ignition {
-    pub fn init(query: Query) -> Result<Engine> {
-        let mut mast = Mast::path("db-dump.tar.gz");
-        let carriage = mast.load()?;
-        Ok(Engine::new(query, carriage))
+    pub fn init_with_config(config: Config) -> Result<Engine> {
+        let rack = Self::load(&config)?;
+        Ok(Engine::new(rack, config))
     }
 
-    pub fn init_with_config(query: Query, config: Config) -> Result<Engine> {
-        let carriage = Mast::path("db-dump.tar.gz").config(config).load()?;
-        Ok(Engine::new(query, carriage))
+    fn load(config: &Config) -> Result<Rack> {
+        let carriage = Mast::path(config.dump_path())
+            .config(config.clone())
+            .load()?;
+        Ok(Rack::from(carriage))
     }
 }
 
 impl Engine {
-    pub fn new(query: Query, carriage: Carriage) -> Self {
-        Engine { query, carriage }
+    pub(crate) fn new(rack: Rack, config: Config) -> Self {
+        Engine { rack, config }
     }
 
-    pub fn run(&mut self) -> Result<Option<UnrolledCrate>> {
-        self.query.apply_to_carriage(&mut self.carriage)
+    /// Loads the graph `config` points at, from the cache when it's still good for the dump.
+    ///
+    /// # Errors
+    ///
+    /// When the dump has to be read and can't be, doesn't load cleanly under `strict`, or one of
+    /// the configured registries fails to merge.
+    pub fn load(config: Config) -> Result<Self> {
+        Ignition::init_with_config(config)
     }
 
-    pub fn process_output(&self) -> Result<()> {
-        todo!("next stage")
+    /// Downloads the dump `config` points at, checked against `sha256` or else the checksum
+    /// published next to it, and rebuilds the graph and its cache from it.
+    ///
+    /// # Errors
+    ///
+    /// When the download fails or doesn't match the checksum, or the new dump doesn't load.
+    pub fn update(config: Config, sha256: Option<String>) -> Result<Self> {
+        Delivery::for_config(&config).with_sha256(sha256).fetch()?;
+        Self::load(Config {
+            fresh: true,
+            ..config
+        })
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.config.format = format;
+    }
+
+    /// Rebuilds the graph from the dump, refreshing the cache along the way.
+    ///
+    /// # Errors
+    ///
+    /// Like [`Engine::load`], keeping the graph loaded before.
+    pub fn reload(&mut self) -> Result<()> {
+        let config = Config {
+            fresh: true,
+            ..self.config.clone()
+        };
+        self.rack = Ignition::load(&config)?;
+        Ok(())
+    }
+
+    /// Applies what the registry index at `location`, a checkout or the URL of a sparse mirror,
+    /// knows beyond the dump and caches the result. Only `names` are refreshed, or every crate
+    /// a checkout has if none are given; a mirror needs them. The rack is thawed for that and
+    /// frozen again afterwards, whether the update went through or not.
+    ///
+    /// # Errors
+    ///
+    /// When no names are given for a mirror, an index file can't be fetched or parsed, or the
+    /// cache can't be written.
+    pub fn apply_index(&mut self, location: &str, names: &[String]) -> Result<IndexDelta> {
+        let source = IndexSource::new(location);
+        let carriage = Carriage::from(std::mem::take(&mut self.rack));
+        let applied = carriage
+            .apply_index(&source, names, None, &self.config.registries)
+            .and_then(|delta| {
+                Mast::path(self.config.dump_path())
+                    .config(self.config.clone())
+                    .store_contents(&CarriageSer::from_carriage(&carriage))?;
+                Ok(delta)
+            });
+        self.rack = Rack::from(carriage);
+        applied
+    }
+
+    /// Every crate name the graph knows, sorted.
+    #[must_use]
+    pub fn crate_names(&self) -> Vec<String> {
+        self.rack.crate_names()
+    }
+
+    /// Runs `query` against the loaded graph, `None` when the crate isn't known.
+    #[must_use]
+    pub fn query(&self, query: &Query) -> Option<UnrolledCrate> {
+        query.apply_to_rack(&self.rack)
+    }
+
+    /// Unrolls a local workspace from its `Cargo.toml`, pinning registry packages to the
+    /// versions in the accompanying `Cargo.lock`.
+    ///
+    /// # Errors
+    ///
+    /// When a manifest or the lock file can't be read or parsed, or a path dependency points
+    /// nowhere.
+    pub fn run_manifest<P: AsRef<Path>>(&self, manifest: P) -> Result<UnrolledCrate> {
+        let workspace = Workspace::open(manifest)?;
+        let route = Route {
+            pins: Some(workspace.pins()),
+            ..Route::default()
+        };
+        workspace.unroll(&self.rack, &route)
+    }
+
+    /// Prints `results` on stdout in the configured format.
+    ///
+    /// # Errors
+    ///
+    /// When stdout can't be written to.
+    pub fn process_output(&self, results: Option<&UnrolledCrate>) -> Result<()> {
+        eprintln!("answered from the {}", self.rack.waybill.describe_age());
+        let stdout = std::io::stdout();
+        self.config.format.render(results, &mut stdout.lock())
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Config {
     pub fresh: bool,
+    /// Fail on dump rows that don't deserialize or hang off nothing, instead of reporting them.
+    pub strict: bool,
+    pub format: Format,
+    pub lager: Lager,
+    pub dump: Option<PathBuf>,
+    pub cache_dir: PathBuf,
+    pub dump_url: String,
+    /// Registries merged into the graph alongside crates.io.
+    pub registries: Vec<Registry>,
+    /// Draw how far loading the dump got on stderr.
+    pub progress: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fresh: false,
+            strict: false,
+            format: Format::default(),
+            lager: Lager::default(),
+            dump: None,
+            cache_dir: xdg_dir("XDG_CACHE_HOME", ".cache")
+                .map_or_else(|| PathBuf::from("."), |dir| dir.join("forklift")),
+            dump_url: DUMP_URL.to_owned(),
+            registries: Vec::new(),
+            progress: false,
+        }
+    }
 }
 
 impl Config {
+    #[must_use]
     #[allow(clippy::needless_update)]
     pub fn fresh() -> Self {
         Config {
@@ -54,4 +199,621 @@ impl Config {
             ..Default::default()
         }
     }
+
+    /// Rebuilds the graph from the dump even if the cache is still good.
+    #[must_use]
+    pub fn with_fresh(mut self, fresh: bool) -> Self {
+        self.fresh = fresh;
+        self
+    }
+
+    #[must_use]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    #[must_use]
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    #[must_use]
+    pub fn with_lager(mut self, lager: Lager) -> Self {
+        self.lager = lager;
+        self
+    }
+
+    #[must_use]
+    pub fn with_dump(mut self, dump: PathBuf) -> Self {
+        self.dump = Some(dump);
+        self
+    }
+
+    #[must_use]
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    #[must_use]
+    pub fn with_dump_url(mut self, dump_url: String) -> Self {
+        self.dump_url = dump_url;
+        self
+    }
+
+    #[must_use]
+    pub fn with_registries(mut self, registries: Vec<Registry>) -> Self {
+        self.registries = registries;
+        self
+    }
+
+    #[must_use]
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// The dump to load, `db-dump.tar.gz` in the cache directory unless one was given. A dump
+    /// left in the working directory, where it used to go, is still picked up while the cache
+    /// directory has none.
+    #[must_use]
+    pub fn dump_path(&self) -> PathBuf {
+        self.dump_path_in(Path::new(""))
+    }
+
+    /// `dump_path` with `working_dir` standing in for the working directory.
+    fn dump_path_in(&self, working_dir: &Path) -> PathBuf {
+        if let Some(dump) = &self.dump {
+            return dump.clone();
+        }
+        let cached = self.cache_dir.join(DUMP_FILE);
+        let legacy = working_dir.join(DUMP_FILE);
+        if !cached.exists() && legacy.is_file() {
+            legacy
+        } else {
+            cached
+        }
+    }
+
+    /// The defaults overlaid with `$XDG_CONFIG_HOME/forklift/config.ron`, if there is one.
+    ///
+    /// # Errors
+    ///
+    /// When the file is there but can't be read or parsed.
+    pub fn from_config_file() -> Result<Self> {
+        Self::from_config_home(xdg_dir("XDG_CONFIG_HOME", ".config"))
+    }
+
+    /// Where the interactive session keeps its history, `$XDG_STATE_HOME/forklift/history`.
+    #[must_use]
+    pub fn history_path() -> PathBuf {
+        xdg_dir("XDG_STATE_HOME", ".local/state").map_or_else(
+            || PathBuf::from(".forklift_history"),
+            |dir| dir.join("forklift").join("history"),
+        )
+    }
+
+    fn from_config_home(config_home: Option<PathBuf>) -> Result<Self> {
+        let Some(path) = config_home
+            .map(|dir| dir.join("forklift").join("config.ron"))
+            .filter(|path| path.is_file())
+        else {
+            return Ok(Config::default());
+        };
+
+        let file: ConfigFile = ron::de::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        Ok(file.apply(Config::default()))
+    }
+}
+
+/// The settings `config.ron` may carry, anything left out keeps its default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub format: Option<Format>,
+    pub cache_format: Option<Lager>,
+    pub dump: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub dump_url: Option<String>,
+    pub registries: Option<Vec<Registry>>,
+}
+
+impl ConfigFile {
+    /// Overrides whatever `config` sets with the values given here.
+    pub fn apply(self, mut config: Config) -> Config {
+        if let Some(format) = self.format {
+            config.format = format;
+        }
+        if let Some(lager) = self.cache_format {
+            config.lager = lager;
+        }
+        if let Some(dump) = self.dump {
+            config.dump = Some(dump);
+        }
+        if let Some(cache_dir) = self.cache_dir {
+            config.cache_dir = cache_dir;
+        }
+        if let Some(dump_url) = self.dump_url {
+            config.dump_url = dump_url;
+        }
+        if let Some(registries) = self.registries {
+            config.registries = registries;
+        }
+        config
+    }
+}
+
+/// An XDG base directory, falling back to `$HOME/<fallback>` when the variable is unset or
+/// not absolute as the spec asks.
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    base_dir(env::var_os(var), env::var_os("HOME"), fallback)
+}
+
+fn base_dir(dir: Option<OsString>, home: Option<OsString>, fallback: &str) -> Option<PathBuf> {
+    dir.map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| PathBuf::from(home).join(fallback)))
+}
+
+/// Fetches a fresh dump into `<dump>.part`, resuming whatever an earlier attempt left there,
+/// and only moves it over the old dump once size and checksum check out.
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    url: String,
+    dump: PathBuf,
+    sha256: Option<String>,
+}
+
+impl Delivery {
+    pub fn new<P: AsRef<Path>>(url: &str, dump: P) -> Self {
+        Self {
+            url: url.to_owned(),
+            dump: dump.as_ref().to_owned(),
+            sha256: None,
+        }
+    }
+
+    pub fn for_config(config: &Config) -> Self {
+        Self::new(&config.dump_url, config.dump_path())
+    }
+
+    /// Checks the download against `sha256` instead of the `<url>.sha256` published next to it.
+    #[must_use]
+    pub fn with_sha256(mut self, sha256: Option<String>) -> Self {
+        self.sha256 = sha256.map(|sha256| sha256.to_lowercase());
+        self
+    }
+
+    fn partial_path(&self) -> PathBuf {
+        self.sibling(".part")
+    }
+
+    /// The `ETag` or `Last-Modified` of the response the part was started from.
+    fn validator_path(&self) -> PathBuf {
+        self.sibling(".part.validator")
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut path = self.dump.clone().into_os_string();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+
+    pub fn fetch(&self) -> Result<()> {
+        if let Some(dir) = self.dump.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let part = self.partial_path();
+        self.download(&part)?;
+        let _ = fs::remove_file(self.validator_path());
+
+        match self.sha256.clone().or_else(|| self.published_sha256()) {
+            Some(expected) => {
+                let actual = Waybill::hash(&part)?;
+                if actual != expected {
+                    fs::remove_file(&part)?;
+                    bail!(
+                        "checksum mismatch for {}: expected {expected}, got {actual}",
+                        self.url
+                    );
+                }
+            }
+            None => eprintln!(
+                "no checksum published for {}, only the size was checked",
+                self.url
+            ),
+        }
+
+        fs::rename(&part, &self.dump)?;
+        Ok(())
+    }
+
+    fn download(&self, part: &Path) -> Result<()> {
+        let validator_path = self.validator_path();
+        let validator = fs::read_to_string(&validator_path).ok();
+        // Without a validator there is no telling whether the part still belongs to the file
+        // the server has now, so it is only resumed with one.
+        let offset = if validator.is_some() {
+            fs::metadata(part).map_or(0, |metadata| metadata.len())
+        } else {
+            0
+        };
+        let mut request = ureq::get(&self.url);
+        if let Some(validator) = validator.as_deref()
+            && offset > 0
+        {
+            eprintln!("resuming {} at {offset} bytes", self.url);
+            request = request
+                .set("Range", &format!("bytes={offset}-"))
+                .set("If-Range", validator);
+        } else {
+            eprintln!("downloading {}", self.url);
+        }
+
+        let response = match request.call() {
+            Ok(response) => response,
+            // The part already holds everything there is to get.
+            Err(ureq::Error::Status(416, _)) if offset > 0 => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        // A server ignoring the range, or holding a different file than the one the part was
+        // started from, answers with the whole file; start over in that case.
+        let resumed = response.status() == 206;
+        let expected = if resumed {
+            response
+                .header("Content-Range")
+                .and_then(|range| range.rsplit_once('/'))
+                .and_then(|(_, total)| total.parse::<u64>().ok())
+        } else {
+            if let Some(validator) = response
+                .header("ETag")
+                .or_else(|| response.header("Last-Modified"))
+            {
+                fs::write(&validator_path, validator)?;
+            } else {
+                let _ = fs::remove_file(&validator_path);
+            }
+            response
+                .header("Content-Length")
+                .and_then(|length| length.parse::<u64>().ok())
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(part)?;
+        io::copy(&mut response.into_reader(), &mut file)?;
+        file.sync_all()?;
+
+        let size = fs::metadata(part)?.len();
+        if let Some(expected) = expected
+            && size != expected
+        {
+            bail!(
+                "{} is {size} bytes, expected {expected}; run update again to resume",
+                part.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Anything but a hex SHA-256 digest, e.g. an error page served with a 200, counts as none.
+    fn published_sha256(&self) -> Option<String> {
+        let published = ureq::get(&format!("{}.sha256", self.url))
+            .call()
+            .ok()?
+            .into_string()
+            .ok()?;
+        published
+            .split_whitespace()
+            .next()
+            .filter(|sha256| sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit()))
+            .map(str::to_lowercase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::scratch;
+    use sha2::{Digest, Sha256};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// What the stand-in currently serves.
+    struct Shelf {
+        body: Vec<u8>,
+        sha256: Option<String>,
+        /// Hang up after this many bytes of the next dump response.
+        cut: Option<usize>,
+    }
+
+    /// Serves the shelf at `/db-dump.tar.gz` with an `ETag`, honouring `Range` and `If-Range`,
+    /// and its `sha256` next to it.
+    struct StandIn {
+        url: String,
+        shelf: Arc<Mutex<Shelf>>,
+        ranges: Arc<Mutex<Vec<String>>>,
+        if_ranges: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StandIn {
+        fn start(body: &[u8], sha256: Option<String>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/db-dump.tar.gz", listener.local_addr().unwrap());
+            let server = Self {
+                url,
+                shelf: Arc::new(Mutex::new(Shelf {
+                    body: body.to_vec(),
+                    sha256,
+                    cut: None,
+                })),
+                ranges: Arc::default(),
+                if_ranges: Arc::default(),
+            };
+            let (shelf, ranges, if_ranges) = (
+                Arc::clone(&server.shelf),
+                Arc::clone(&server.ranges),
+                Arc::clone(&server.if_ranges),
+            );
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    Self::answer(stream, &shelf, &ranges, &if_ranges);
+                }
+            });
+            server
+        }
+
+        fn cut_after(&self, bytes: usize) {
+            self.shelf.lock().unwrap().cut = Some(bytes);
+        }
+
+        fn replace(&self, body: &[u8]) {
+            self.shelf.lock().unwrap().body = body.to_vec();
+        }
+
+        fn answer(
+            mut stream: TcpStream,
+            shelf: &Mutex<Shelf>,
+            ranges: &Mutex<Vec<String>>,
+            if_ranges: &Mutex<Vec<String>>,
+        ) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let (mut range, mut if_range) = (None, None);
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("range") {
+                        range = Some(value.trim().to_owned());
+                    } else if name.eq_ignore_ascii_case("if-range") {
+                        if_range = Some(value.trim().to_owned());
+                    }
+                }
+            }
+
+            let mut shelf = shelf.lock().unwrap();
+            let etag = etag_of(&shelf.body);
+            let mut cut = None;
+            let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+            let (status, headers, payload): (&str, String, Vec<u8>) = match path {
+                "/db-dump.tar.gz.sha256" => match &shelf.sha256 {
+                    Some(sha256) => ("200 OK", String::new(), sha256.as_bytes().to_vec()),
+                    None => ("404 Not Found", String::new(), Vec::new()),
+                },
+                "/db-dump.tar.gz" => {
+                    let body = &shelf.body;
+                    // A stale `If-Range` turns the request into a plain one for the whole file.
+                    let start = range
+                        .as_deref()
+                        .filter(|_| if_range.as_deref().is_none_or(|tag| tag == etag))
+                        .and_then(|range| range.strip_prefix("bytes="))
+                        .and_then(|range| range.strip_suffix('-'))
+                        .and_then(|start| start.parse::<usize>().ok());
+                    ranges.lock().unwrap().extend(range);
+                    if_ranges.lock().unwrap().extend(if_range);
+                    let response = match start {
+                        Some(start) if start >= body.len() => {
+                            ("416 Range Not Satisfiable", String::new(), Vec::new())
+                        }
+                        Some(start) => (
+                            "206 Partial Content",
+                            format!(
+                                "ETag: {etag}\r\nContent-Range: bytes {start}-{}/{}\r\n",
+                                body.len() - 1,
+                                body.len()
+                            ),
+                            body[start..].to_vec(),
+                        ),
+                        None => ("200 OK", format!("ETag: {etag}\r\n"), body.to_vec()),
+                    };
+                    cut = shelf.cut.take();
+                    response
+                }
+                _ => ("404 Not Found", String::new(), Vec::new()),
+            };
+
+            let sent = cut.map_or(payload.len(), |cut| cut.min(payload.len()));
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n",
+                payload.len()
+            );
+            let _ = stream.write_all(&payload[..sent]);
+        }
+    }
+
+    const BODY: &[u8] = b"not really a tarball, but the bytes are all that matter here";
+    const NEWER: &[u8] = b"a later dump, published while the first one was half fetched";
+
+    fn sha256_of(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    fn etag_of(bytes: &[u8]) -> String {
+        format!("\"{}\"", &sha256_of(bytes)[..16])
+    }
+
+    #[test]
+    fn downloads_and_swaps_in_the_dump() {
+        let server = StandIn::start(BODY, Some(format!("{}  db-dump.tar.gz\n", sha256_of(BODY))));
+        let dir = scratch("update-fresh");
+        let dump = dir.join("db-dump.tar.gz");
+        fs::write(&dump, b"the old dump").unwrap();
+
+        Delivery::new(&server.url, &dump).fetch().unwrap();
+
+        assert_eq!(fs::read(&dump).unwrap(), BODY);
+        assert!(!dir.join("db-dump.tar.gz.part").exists());
+        assert!(!dir.join("db-dump.tar.gz.part.validator").exists());
+        assert!(server.ranges.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn resumes_a_partial_download() {
+        let server = StandIn::start(BODY, None);
+        let dir = scratch("update-resume");
+        let dump = dir.join("db-dump.tar.gz");
+        let delivery = Delivery::new(&server.url, &dump).with_sha256(Some(sha256_of(BODY)));
+
+        server.cut_after(20);
+        assert!(delivery.fetch().is_err());
+        assert_eq!(fs::read(dir.join("db-dump.tar.gz.part")).unwrap(), &BODY[..20]);
+        delivery.fetch().unwrap();
+
+        assert_eq!(fs::read(&dump).unwrap(), BODY);
+        assert_eq!(*server.ranges.lock().unwrap(), ["bytes=20-"]);
+        assert_eq!(*server.if_ranges.lock().unwrap(), [etag_of(BODY)]);
+    }
+
+    #[test]
+    fn starts_over_when_the_dump_changed_between_fetches() {
+        let server = StandIn::start(BODY, None);
+        let dir = scratch("update-changed");
+        let dump = dir.join("db-dump.tar.gz");
+        let delivery = Delivery::new(&server.url, &dump).with_sha256(Some(sha256_of(NEWER)));
+
+        server.cut_after(20);
+        assert!(delivery.fetch().is_err());
+        server.replace(NEWER);
+        delivery.fetch().unwrap();
+
+        assert_eq!(fs::read(&dump).unwrap(), NEWER);
+        assert_eq!(*server.if_ranges.lock().unwrap(), [etag_of(BODY)]);
+    }
+
+    #[test]
+    fn does_not_resume_a_part_without_a_validator() {
+        let server = StandIn::start(BODY, None);
+        let dir = scratch("update-unvalidated");
+        let dump = dir.join("db-dump.tar.gz");
+        fs::write(dir.join("db-dump.tar.gz.part"), &NEWER[..20]).unwrap();
+
+        Delivery::new(&server.url, &dump)
+            .with_sha256(Some(sha256_of(BODY)))
+            .fetch()
+            .unwrap();
+
+        assert_eq!(fs::read(&dump).unwrap(), BODY);
+        assert!(server.ranges.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_old_dump_on_a_checksum_mismatch() {
+        let server = StandIn::start(BODY, Some(sha256_of(b"something else")));
+        let dir = scratch("update-mismatch");
+        let dump = dir.join("db-dump.tar.gz");
+        fs::write(&dump, b"the old dump").unwrap();
+
+        let error = Delivery::new(&server.url, &dump).fetch().unwrap_err();
+
+        assert!(error.to_string().contains("checksum mismatch"));
+        assert_eq!(fs::read(&dump).unwrap(), b"the old dump");
+        assert!(!dir.join("db-dump.tar.gz.part").exists());
+    }
+
+    #[test]
+    fn ignores_a_published_checksum_that_is_not_one() {
+        let server = StandIn::start(BODY, Some("<html>not found</html>".to_owned()));
+        let dir = scratch("update-garbled");
+        let dump = dir.join("db-dump.tar.gz");
+
+        Delivery::new(&server.url, &dump).fetch().unwrap();
+
+        assert_eq!(fs::read(&dump).unwrap(), BODY);
+    }
+
+    #[test]
+    fn config_file_overlays_the_defaults() -> Result<()> {
+        let home = scratch("config-home");
+        fs::create_dir_all(home.join("forklift"))?;
+        fs::write(
+            home.join("forklift").join("config.ron"),
+            r#"(format: Some(json), cache_dir: Some("/var/cache/forklift"))"#,
+        )?;
+
+        let config = Config::from_config_home(Some(home.to_path_buf()))?;
+        assert_eq!(config.format, Format::Json);
+        assert_eq!(config.cache_dir, Path::new("/var/cache/forklift"));
+        assert_eq!(config.lager, Lager::default());
+        assert_eq!(config.dump_url, DUMP_URL);
+
+        fs::write(home.join("forklift").join("config.ron"), "(format: Some(xml))")?;
+        let error = Config::from_config_home(Some(home.to_path_buf())).unwrap_err();
+        assert!(error.to_string().starts_with("parsing "), "{error}");
+
+        let empty = scratch("config-empty");
+        let config = Config::from_config_home(Some(empty.to_path_buf()))?;
+        assert_eq!(config.format, Format::default());
+        Ok(())
+    }
+
+    #[test]
+    fn base_dirs_fall_back_to_home() {
+        let cases = [
+            (Some("/xdg/cache"), Some("/home/me"), Some("/xdg/cache")),
+            (Some("relative"), Some("/home/me"), Some("/home/me/.cache")),
+            (None, Some("/home/me"), Some("/home/me/.cache")),
+            (None, None, None),
+        ];
+        for (dir, home, expected) in cases {
+            assert_eq!(
+                base_dir(dir.map(OsString::from), home.map(OsString::from), ".cache"),
+                expected.map(PathBuf::from),
+                "{dir:?} {home:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dump_left_in_the_working_directory_is_still_found() -> Result<()> {
+        let dir = scratch("dump-fallback");
+        let (working_dir, cache_dir) = (dir.join("work"), dir.join("cache"));
+        fs::create_dir_all(&working_dir)?;
+        fs::create_dir_all(&cache_dir)?;
+        let config = Config::default().with_cache_dir(cache_dir.clone());
+
+        assert_eq!(config.dump_path_in(&working_dir), cache_dir.join(DUMP_FILE));
+        fs::write(working_dir.join(DUMP_FILE), BODY)?;
+        assert_eq!(config.dump_path_in(&working_dir), working_dir.join(DUMP_FILE));
+        fs::write(cache_dir.join(DUMP_FILE), BODY)?;
+        assert_eq!(config.dump_path_in(&working_dir), cache_dir.join(DUMP_FILE));
+
+        let given = config.with_dump(dir.join("elsewhere.tar.gz"));
+        assert_eq!(given.dump_path_in(&working_dir), dir.join("elsewhere.tar.gz"));
+        Ok(())
+    }
 }
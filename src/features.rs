@@ -0,0 +1,173 @@
+use crate::store::Skid;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The outcome of resolving a feature table: which features a release is built with, which of
+/// its optional dependencies that switches on, and the features forwarded to each dependency
+/// through `dep/feature` entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Activation {
+    enabled: BTreeSet<String>,
+    dependencies: BTreeSet<String>,
+    forwarded: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Activation {
+    pub fn resolve<I>(table: &BTreeMap<String, Vec<String>>, requested: I, default: bool) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut activation = Self::default();
+        let mut weak = Vec::new();
+        let mut queue = requested.into_iter().collect::<Vec<_>>();
+        if default && table.contains_key("default") {
+            queue.push("default".to_owned());
+        }
+
+        while let Some(feature) = queue.pop() {
+            if let Some((dependency, dependency_feature)) = feature.split_once('/') {
+                if let Some(dependency) = dependency.strip_suffix('?') {
+                    weak.push((dependency.to_owned(), dependency_feature.to_owned()));
+                    continue;
+                }
+
+                activation.dependencies.insert(dependency.to_owned());
+                activation.forward(dependency, dependency_feature);
+                if table.contains_key(dependency) {
+                    queue.push(dependency.to_owned());
+                }
+            } else if let Some(dependency) = feature.strip_prefix("dep:") {
+                activation.dependencies.insert(dependency.to_owned());
+            } else if !activation.enabled.contains(&feature) {
+                match table.get(&feature) {
+                    Some(implied) => {
+                        activation.enabled.insert(feature);
+                        queue.extend(implied.iter().cloned());
+                    }
+                    // Optional dependencies double as features, unless the table refers to one
+                    // as `dep:<name>`, which hides its implicit feature the way Cargo does.
+                    None if feature == "default" || Self::hidden(table, &feature) => {}
+                    None => {
+                        activation.enabled.insert(feature.clone());
+                        activation.dependencies.insert(feature);
+                    }
+                }
+            }
+        }
+
+        for (dependency, feature) in weak {
+            if activation.dependencies.contains(&dependency) {
+                activation.forward(&dependency, &feature);
+            }
+        }
+        activation
+    }
+
+    fn hidden(table: &BTreeMap<String, Vec<String>>, dependency: &str) -> bool {
+        table
+            .values()
+            .flatten()
+            .any(|implied| implied.strip_prefix("dep:") == Some(dependency))
+    }
+
+    fn forward(&mut self, dependency: &str, feature: &str) {
+        self.forwarded
+            .entry(dependency.to_owned())
+            .or_default()
+            .insert(feature.to_owned());
+    }
+
+    /// Whether the edge is compiled in at all, `name` being the name the dependency goes by in
+    /// the manifest.
+    pub fn enables(&self, name: &str, skid: &Skid) -> bool {
+        !skid.optional || self.dependencies.contains(name)
+    }
+
+    /// Resolves the features the dependency behind `skid` ends up with.
+    pub fn for_dependency(
+        &self,
+        name: &str,
+        skid: &Skid,
+        table: &BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        let forwarded = self.forwarded.get(name).into_iter().flatten().cloned();
+        Self::resolve(
+            table,
+            skid.features.iter().cloned().chain(forwarded),
+            skid.default_features,
+        )
+    }
+
+    pub fn enabled(&self) -> Vec<String> {
+        self.enabled.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::DependencyKind;
+
+    fn table() -> BTreeMap<String, Vec<String>> {
+        [
+            ("default", vec!["std"]),
+            ("std", vec!["dep:serde", "log?/std"]),
+            ("derive", vec!["serde/derive"]),
+        ]
+        .into_iter()
+        .map(|(feature, implied)| {
+            let implied = implied.into_iter().map(str::to_owned).collect();
+            (feature.to_owned(), implied)
+        })
+        .collect()
+    }
+
+    fn optional() -> Skid {
+        Skid {
+            optional: true,
+            ..Skid::new_with_dependency(1, "^1".to_owned(), DependencyKind::Normal)
+        }
+    }
+
+    #[test]
+    fn dep_prefix_enables_the_dependency_without_a_feature() {
+        let activation = Activation::resolve(&table(), [], true);
+
+        assert_eq!(activation.enabled(), ["default", "std"]);
+        assert!(activation.enables("serde", &optional()));
+        assert!(!activation.enables("log", &optional()));
+    }
+
+    #[test]
+    fn weak_features_only_forward_to_enabled_dependencies() {
+        let without = Activation::resolve(&table(), [], true);
+        assert!(!without.forwarded.contains_key("log"));
+
+        let with = Activation::resolve(&table(), ["log".to_owned()], true);
+        assert!(with.enables("log", &optional()));
+        assert_eq!(with.forwarded["log"], BTreeSet::from(["std".to_owned()]));
+    }
+
+    #[test]
+    fn dependency_features_switch_the_dependency_on() {
+        let activation = Activation::resolve(&table(), ["derive".to_owned()], false);
+
+        assert_eq!(activation.enabled(), ["derive"]);
+        assert!(activation.enables("serde", &optional()));
+        assert_eq!(
+            activation.forwarded["serde"],
+            BTreeSet::from(["derive".to_owned()])
+        );
+    }
+
+    #[test]
+    fn dep_prefix_hides_the_implicit_feature() {
+        let activation = Activation::resolve(&table(), ["serde".to_owned()], false);
+
+        assert!(activation.enabled().is_empty());
+        assert!(!activation.enables("serde", &optional()));
+
+        let implicit = Activation::resolve(&table(), ["log".to_owned()], false);
+        assert_eq!(implicit.enabled(), ["log"]);
+        assert!(implicit.enables("log", &optional()));
+    }
+}
@@ -0,0 +1,86 @@
+//! Dumps in the crates.io format for tests, written the way crates.io packs them.
+use anyhow::Result;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+pub const CRATES: &str = "\
+created_at,description,homepage,id,max_features,max_upload_size,name,repository,updated_at
+2017-02-01 00:00:00,,,1,,,serde,,2024-01-01 00:00:00
+2017-02-01 00:00:00,,,2,,,serde_derive,,2024-01-01 00:00:00
+";
+
+pub const VERSIONS: &str = "\
+bin_names,checksum,crate_id,crate_size,created_at,downloads,features,has_lib,id,license,links,num,published_by,rust_version,updated_at,yanked
+{},,1,,2024-01-01 00:00:00,10,\"{\"\"derive\"\":[\"\"serde_derive\"\"]}\",t,10,MIT,,1.0.200,,,2024-01-01 00:00:00,f
+{},,2,,2024-01-01 00:00:00,10,{},t,20,MIT,,1.0.200,,,2024-01-01 00:00:00,f
+{},,2,,2023-01-01 00:00:00,10,{},t,21,MIT,,1.0.100,,,2023-01-01 00:00:00,t
+";
+
+pub const DEPENDENCIES: &str = "\
+crate_id,default_features,explicit_name,features,id,kind,optional,req,target,version_id
+2,t,,{},100,0,t,=1.0.200,,10
+";
+
+/// `serde` at `1.0.200` depending on `serde_derive`, which also has a yanked `1.0.100`.
+pub fn write_dump(path: &Path) -> Result<()> {
+    write_tables(path, CRATES, VERSIONS, DEPENDENCIES)
+}
+
+pub fn write_tables(path: &Path, crates: &str, versions: &str, dependencies: &str) -> Result<()> {
+    fs::write(path, tarball(crates, versions, dependencies)?)?;
+    Ok(())
+}
+
+/// The tables packed into a gzipped tarball under `data/`, the way the dump has them.
+pub fn tarball(crates: &str, versions: &str, dependencies: &str) -> Result<Vec<u8>> {
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, contents) in [
+        ("data/crates.csv", crates),
+        ("data/versions.csv", versions),
+        ("data/dependencies.csv", dependencies),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, contents.as_bytes())?;
+    }
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// A directory of its own under the temporary directory, removed again once dropped, so a test
+/// that fails halfway doesn't leave it behind.
+pub struct Scratch(PathBuf);
+
+/// A fresh, empty scratch directory for the test called `name`. Names have to be unique, tests
+/// run side by side in one process.
+pub fn scratch(name: &str) -> Scratch {
+    let dir = env::temp_dir().join(format!("forklift-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    Scratch(dir)
+}
+
+impl Deref for Scratch {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for Scratch {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,10 +1,12 @@
 use crate::carriage::Carriage;
-use crate::crusher::Crusher;
+use crate::crusher::Lager;
 use crate::download::Config;
 use crate::serproxy::CarriageSer;
+use crate::waybill::Waybill;
 use anyhow::Result;
+use clap::ValueEnum;
 use serde::Deserialize;
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
@@ -28,21 +30,173 @@ impl Mast {
         self
     }
 
+    /// Where the cache is written, named after the configured format.
+    pub fn lager_path(&self) -> PathBuf {
+        self.config.cache_dir.join(self.config.lager.file_name())
+    }
+
+    /// The cache to read and its format: the one written in the configured format, or else one
+    /// left in another. The extension tells which format a file is in, the configuration only
+    /// picks the one written.
+    fn find_lager(&self) -> Option<(Lager, PathBuf)> {
+        let configured = self.config.lager;
+        let others = Lager::value_variants()
+            .iter()
+            .copied()
+            .filter(|lager| *lager != configured);
+        std::iter::once(configured)
+            .chain(others)
+            .map(|lager| self.config.cache_dir.join(lager.file_name()))
+            .find(|path| path.is_file())
+            .and_then(|path| Some((Lager::of_path(&path)?, path)))
+    }
+
     pub fn load(&mut self) -> Result<Carriage> {
         if !self.config.fresh
-            && let Ok(mut file) = OpenOptions::new().read(true).open("lager.fork")
+            && let Some(cached) = self.load_lager()
         {
-            let mut buffer = Vec::new();
-            let _ = file.read_to_end(&mut buffer)?;
-            Self::uncrush(buffer).map(Into::into)
+            Ok(cached.into())
         } else {
-            let carriage = Carriage::unarchive(&self.path)?;
+            let (mut carriage, tally) =
+                Carriage::unarchive_tallied(&self.path, self.config.progress)?;
+            tally.settle(self.config.strict)?;
+            carriage.merge_registries(&self.config.registries, self.config.strict)?;
             let _ = self.store_contents(&CarriageSer::from_carriage(&carriage));
             Ok(carriage)
         }
     }
 
+    /// The cached graph, unless it is missing, unreadable, in an earlier format, was built from
+    /// another dump or other registries, or from rows a strict load would refuse.
+    fn load_lager(&self) -> Option<CarriageSer> {
+        let (format, lager) = self.find_lager()?;
+        let mut buffer = Vec::new();
+        let _ = OpenOptions::new()
+            .read(true)
+            .open(&lager)
+            .ok()?
+            .read_to_end(&mut buffer)
+            .ok()?;
+        let cached = format.uncrush(buffer).ok()?;
+
+        if cached.waybill.format != Waybill::FORMAT {
+            eprintln!(
+                "{} was written by another version of forklift, rebuilding",
+                lager.display()
+            );
+            None
+        } else if self.config.strict && !cached.waybill.clean {
+            // Loading the dump again is what reports the rows a strict load refuses.
+            eprintln!(
+                "{} was built from a dump that didn't load cleanly, rebuilding",
+                lager.display()
+            );
+            None
+        } else if !self.path.exists() {
+            // Without the dump there is nothing to rebuild from, a stale cache beats none.
+            Some(cached)
+        } else if !cached.waybill.matches(&self.path).unwrap_or(false) {
+            eprintln!(
+                "{} changed since {} was built, rebuilding",
+                self.path.display(),
+                lager.display()
+            );
+            None
+        } else if !self.same_registries(&cached.waybill) {
+            eprintln!(
+                "the registries changed since {} was built, rebuilding",
+                lager.display()
+            );
+            None
+        } else {
+            Some(cached)
+        }
+    }
+
+    fn same_registries(&self, waybill: &Waybill) -> bool {
+        let registries = &self.config.registries;
+        waybill.registries.len() == registries.len()
+            && registries.iter().all(|registry| {
+                waybill
+                    .registries
+                    .get(&registry.name)
+                    .is_some_and(|waybill| registry.is_unchanged(waybill))
+            })
+    }
+
     pub fn store_contents(&self, contents: &CarriageSer) -> Result<()> {
-        self.crush("lager.fork", contents)
+        fs::create_dir_all(&self.config.cache_dir)?;
+        let lager = self.lager_path();
+        self.config.lager.crush(self, &lager, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{scratch, write_dump};
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn a_replaced_dump_invalidates_the_cache() -> Result<()> {
+        let dir = scratch("lager");
+        let dump = dir.join("db-dump.tar.gz");
+        write_dump(&dump)?;
+        let mut mast = Mast::path(&dump);
+        mast.config(Config::default().with_cache_dir(dir.join("cache")));
+        mast.load()?;
+        assert!(mast.load_lager().is_some());
+
+        // Copied over, the same bytes under a new mtime.
+        File::options()
+            .write(true)
+            .open(&dump)?
+            .set_modified(SystemTime::now() + Duration::from_secs(60))?;
+        assert!(mast.load_lager().is_some());
+
+        fs::write(&dump, b"another dump")?;
+        assert!(mast.load_lager().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn a_cache_of_another_format_is_rebuilt() -> Result<()> {
+        let dir = scratch("lager-format");
+        let dump = dir.join("db-dump.tar.gz");
+        write_dump(&dump)?;
+        let mut mast = Mast::path(&dump);
+        mast.config(Config::default().with_cache_dir(dir.join("cache")));
+        mast.load()?;
+
+        let mut cached = mast.load_lager().unwrap();
+        assert_eq!(cached.waybill.format, Waybill::FORMAT);
+        cached.waybill.format = Waybill::FORMAT - 1;
+        mast.store_contents(&cached)?;
+        assert!(mast.load_lager().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn a_cache_is_read_in_the_format_its_extension_tells() -> Result<()> {
+        let dir = scratch("lager-extension");
+        let dump = dir.join("db-dump.tar.gz");
+        write_dump(&dump)?;
+        let config = Config::default().with_cache_dir(dir.join("cache"));
+        let mut bale = Mast::path(&dump);
+        bale.config(config.clone().with_lager(Lager::Bale));
+        bale.load()?;
+
+        // Configured for RON, the cache left as a bale is still read.
+        let mut ron = Mast::path(&dump);
+        ron.config(config.with_lager(Lager::Ron));
+        assert_eq!(ron.find_lager().map(|(lager, _)| lager), Some(Lager::Bale));
+        assert!(ron.load_lager().is_some());
+
+        // Once written as RON that is the one read.
+        ron.store_contents(&bale.load_lager().unwrap())?;
+        assert_eq!(ron.find_lager(), Some((Lager::Ron, ron.lager_path())));
+        assert!(ron.load_lager().is_some());
+        Ok(())
     }
 }
@@ -0,0 +1,280 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// A byte range of the query text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    /// The characters before `start`, which is what columns are counted in.
+    pub chars: usize,
+}
+
+impl Span {
+    /// Where `token` sits in `input`. Every token the parser handles is a slice of the query, so
+    /// its offset follows from the pointers.
+    pub(crate) fn of(input: &str, token: &str) -> Self {
+        let start = (token.as_ptr() as usize)
+            .checked_sub(input.as_ptr() as usize)
+            .filter(|start| start + token.len() <= input.len())
+            .unwrap_or(input.len());
+        Self::between(input, start, (start + token.len()).min(input.len()))
+    }
+
+    /// The empty span right behind `token`, where something was expected to follow.
+    pub(crate) fn after(input: &str, token: &str) -> Self {
+        let end = Self::of(input, token).end;
+        Self::between(input, end, end)
+    }
+
+    pub(crate) fn end_of(input: &str) -> Self {
+        Self::between(input, input.len(), input.len())
+    }
+
+    fn between(input: &str, start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            chars: input.get(..start).map_or(0, |head| head.chars().count()),
+        }
+    }
+
+    /// Counted in characters, so a query with `ä` in it doesn't push the column off.
+    #[must_use]
+    pub fn column(self) -> usize {
+        self.chars + 1
+    }
+}
+
+/// Everything that can be wrong with a query, pointing at the part of it that is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QueryError {
+    /// The query never says which crate to lift.
+    NoLift {
+        span: Span,
+    },
+    /// The conditions given to filter by are blank.
+    NoConditions {
+        span: Span,
+    },
+    /// The query starts with something that isn't a keyword.
+    ExpectedKeyword {
+        found: String,
+        span: Span,
+        suggestion: Option<&'static str>,
+    },
+    RepeatedKeyword {
+        keyword: String,
+        span: Span,
+    },
+    /// The query ended, or the keyword's tokens ran out, before a required part.
+    MissingValue {
+        expected: &'static str,
+        after: String,
+        span: Span,
+    },
+    UnexpectedToken {
+        expected: &'static str,
+        found: String,
+        span: Span,
+        suggestion: Option<&'static str>,
+    },
+    /// The token is in the right place but doesn't parse, e.g. `DEPTH many`.
+    InvalidValue {
+        expected: &'static str,
+        found: String,
+        span: Span,
+    },
+    UnsupportedOperator {
+        operator: String,
+        condition: String,
+        span: Span,
+    },
+}
+
+impl QueryError {
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            QueryError::NoLift { span }
+            | QueryError::NoConditions { span }
+            | QueryError::ExpectedKeyword { span, .. }
+            | QueryError::RepeatedKeyword { span, .. }
+            | QueryError::MissingValue { span, .. }
+            | QueryError::UnexpectedToken { span, .. }
+            | QueryError::InvalidValue { span, .. }
+            | QueryError::UnsupportedOperator { span, .. } => *span,
+        }
+    }
+
+    /// The message followed by the query with the offending part underlined:
+    ///
+    /// ```text
+    /// expected a version requirement after `version` at column 25
+    ///     LIFT serde WHERE version
+    ///                             ^
+    /// ```
+    #[must_use]
+    pub fn diagnose(&self, input: &str) -> String {
+        let span = self.span();
+        let width = input
+            .get(span.start..span.end)
+            .map_or(0, |token| token.chars().count())
+            .max(1);
+        format!(
+            "{self}\n    {input}\n    {}{}",
+            " ".repeat(span.chars),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl Error for QueryError {}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let column = self.span().column();
+        match self {
+            QueryError::NoLift { .. } => write!(f, "expected `LIFT <crate>` in the query")?,
+            QueryError::NoConditions { .. } => write!(f, "expected a condition to filter by")?,
+            QueryError::ExpectedKeyword { found, .. } => write!(
+                f,
+                "expected a keyword such as LIFT, found `{found}` at column {column}"
+            )?,
+            QueryError::RepeatedKeyword { keyword, .. } => {
+                write!(f, "`{keyword}` given twice at column {column}")?;
+            }
+            QueryError::MissingValue {
+                expected, after, ..
+            } => write!(f, "expected {expected} after `{after}` at column {column}")?,
+            QueryError::UnexpectedToken {
+                expected, found, ..
+            } => write!(f, "expected {expected}, found `{found}` at column {column}")?,
+            QueryError::InvalidValue {
+                expected, found, ..
+            } => write!(f, "`{found}` is not {expected} at column {column}")?,
+            QueryError::UnsupportedOperator {
+                operator,
+                condition,
+                ..
+            } => write!(
+                f,
+                "`{condition}` can't be compared with `{operator}` at column {column}"
+            )?,
+        }
+
+        match self {
+            QueryError::ExpectedKeyword {
+                suggestion: Some(suggestion),
+                ..
+            }
+            | QueryError::UnexpectedToken {
+                suggestion: Some(suggestion),
+                ..
+            } => write!(f, ", did you mean `{suggestion}`?"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The candidate `found` most likely is a typo of, if any is close enough.
+pub fn suggest(found: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let found = found.to_lowercase();
+    let tolerance = found.chars().count().max(3) / 3;
+    candidates
+        .iter()
+        .map(|candidate| (distance(&found, &candidate.to_lowercase()), *candidate))
+        .filter(|(distance, _)| *distance <= tolerance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance counting a swap of neighbouring characters as one edit, `lfit` is one away
+/// from `lift`.
+fn distance(left: &str, right: &str) -> usize {
+    let left = left.chars().collect::<Vec<_>>();
+    let right = right.chars().collect::<Vec<_>>();
+    let mut rows = vec![(0..=right.len()).collect::<Vec<_>>()];
+
+    for i in 1..=left.len() {
+        let mut row = vec![i; right.len() + 1];
+        for j in 1..=right.len() {
+            let cost = usize::from(left[i - 1] != right[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && left[i - 1] == right[j - 2] && left[i - 2] == right[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+
+    rows[left.len()][right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnose_underlines_the_span() {
+        let error = QueryError::MissingValue {
+            expected: "a version requirement",
+            after: "version".to_owned(),
+            span: Span::end_of("LIFT serde WHERE version"),
+        };
+        assert_eq!(
+            error.diagnose("LIFT serde WHERE version"),
+            format!(
+                "expected a version requirement after `version` at column 25\n    \
+                 LIFT serde WHERE version\n    {}^",
+                " ".repeat(24)
+            )
+        );
+
+        // The column and the caret are counted in characters, not bytes.
+        let input = "LIFT serde WHERE kind = ä normäl";
+        let token = &input[27..];
+        let error = QueryError::InvalidValue {
+            expected: "normal, build or dev",
+            found: token.to_owned(),
+            span: Span::of(input, token),
+        };
+        assert_eq!(
+            error.diagnose(input),
+            format!(
+                "`normäl` is not normal, build or dev at column 27\n    {input}\n    {}{}",
+                " ".repeat(26),
+                "^".repeat(6)
+            )
+        );
+    }
+
+    #[test]
+    fn distance_counts_swapped_neighbours_once() {
+        let cases = [
+            ("lift", "lift", 0),
+            ("lfit", "lift", 1),
+            ("wehre", "where", 1),
+            ("kitten", "sitting", 3),
+            ("", "and", 3),
+        ];
+        for (left, right, expected) in cases {
+            assert_eq!(distance(left, right), expected, "{left} {right}");
+        }
+    }
+
+    #[test]
+    fn suggest_only_close_candidates() {
+        let keywords = &["LIFT", "WHERE", "REVERSE"];
+        assert_eq!(suggest("lfit", keywords), Some("LIFT"));
+        assert_eq!(suggest("WEHRE", keywords), Some("WHERE"));
+        assert_eq!(suggest("reveres", keywords), Some("REVERSE"));
+        assert_eq!(suggest("serde", keywords), None);
+        assert_eq!(suggest("o", &["OR"]), Some("OR"));
+        assert_eq!(suggest("x", &["OR"]), None);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::store::{DependencyKind, Skid};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Where registry index files come from: a checkout of the index repository or a mirror serving
+/// the sparse protocol over HTTP.
+#[derive(Clone, Debug)]
+pub enum IndexSource {
+    Checkout(PathBuf),
+    Mirror(String),
+}
+
+impl IndexSource {
+    pub fn new(location: &str) -> Self {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            IndexSource::Mirror(location.trim_end_matches('/').to_owned())
+        } else {
+            IndexSource::Checkout(PathBuf::from(location))
+        }
+    }
+
+    /// The path of a crate's file inside the index, `se/rd/serde` and the like. Crate names are
+    /// ASCII, anything else can't have a file.
+    pub fn index_path(name: &str) -> Result<String> {
+        if name.is_empty() || !name.is_ascii() {
+            bail!("{name:?} isn't a crate name");
+        }
+        let name = name.to_ascii_lowercase();
+        Ok(match name.len() {
+            1 => format!("1/{name}"),
+            2 => format!("2/{name}"),
+            3 => format!("3/{}/{name}", &name[..1]),
+            _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+        })
+    }
+
+    /// The index file of `name`, `None` when the index doesn't know the crate.
+    pub fn fetch(&self, name: &str) -> Result<Option<String>> {
+        let path = Self::index_path(name)?;
+        match self {
+            IndexSource::Checkout(root) => match fs::read_to_string(root.join(&path)) {
+                Ok(contents) => Ok(Some(contents)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e).with_context(|| format!("reading {path}")),
+            },
+            IndexSource::Mirror(base) => match ureq::get(&format!("{base}/{path}")).call() {
+                Ok(response) => Ok(Some(response.into_string()?)),
+                Err(ureq::Error::Status(404 | 410, _)) => Ok(None),
+                Err(e) => Err(e).with_context(|| format!("fetching {base}/{path}")),
+            },
+        }
+    }
+
+    /// Every crate a checkout has a file for. A mirror can't be listed.
+    pub fn crate_names(&self) -> Option<Vec<String>> {
+        let IndexSource::Checkout(root) = self else {
+            return None;
+        };
+        let mut names = Vec::new();
+        Self::collect_names(root, 0, &mut names);
+        Some(names)
+    }
+
+    fn collect_names(dir: &Path, depth: usize, names: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || (depth == 0 && name == "config.json") {
+                continue;
+            }
+            if path.is_dir() {
+                Self::collect_names(&path, depth + 1, names);
+            } else {
+                names.push(name);
+            }
+        }
+    }
+}
+
+/// One line of an index file, a single published version.
+#[derive(Debug, Deserialize)]
+pub struct IndexRelease {
+    pub name: String,
+    pub vers: String,
+    #[serde(default)]
+    pub deps: Vec<IndexDependency>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    /// Features using `dep:` or `?` syntax, kept apart so older Cargo versions don't choke.
+    #[serde(default)]
+    features2: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub yanked: bool,
+}
+
+impl IndexRelease {
+    pub fn parse_file(contents: &str) -> Result<Vec<Self>> {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).context("malformed index line"))
+            .collect()
+    }
+
+    pub fn features(&self) -> BTreeMap<String, Vec<String>> {
+        let mut features = self.features.clone();
+        features.extend(self.features2.clone());
+        features
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexDependency {
+    /// The name the dependency goes by in the manifest.
+    pub name: String,
+    pub req: String,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    optional: bool,
+    #[serde(default = "enabled")]
+    default_features: bool,
+    target: Option<String>,
+    kind: Option<String>,
+    /// Set when the dependency lives in another registry than the index it was read from.
+    pub registry: Option<String>,
+    /// The actual crate when the dependency was renamed.
+    package: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl IndexDependency {
+    pub fn crate_name(&self) -> &str {
+        self.package.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn to_skid(&self, crate_id: u32) -> Skid {
+        let kind = self
+            .kind
+            .as_deref()
+            .and_then(DependencyKind::try_from_token)
+            .unwrap_or_default();
+        Skid {
+            name: self.package.as_ref().map(|_| self.name.clone()),
+            optional: self.optional,
+            default_features: self.default_features,
+            features: self.features.clone(),
+            target: self.target.clone(),
+            ..Skid::new_with_dependency(crate_id, self.req.clone(), kind)
+        }
+    }
+}
+
+/// What applying an index changed.
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct IndexDelta {
+    pub crates: usize,
+    pub releases: usize,
+    pub yanked: usize,
+    pub unyanked: usize,
+}
+
+impl Display for IndexDelta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} new crates, {} new releases, {} yanked, {} unyanked",
+            self.crates, self.releases, self.yanked, self.unyanked
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carriage::Carriage;
+    use crate::cell::SichtCell;
+    use crate::fixture::{Scratch, scratch};
+    use crate::store::{Crate, Kiste, Release};
+    use sicht::SichtMap;
+
+    const SERDE: &str = r#"
+{"name":"serde","vers":"0.9.0","deps":[],"features":{},"yanked":false}
+{"name":"serde","vers":"1.0.0","deps":[],"features":{},"yanked":true}
+{"name":"serde","vers":"1.1.0","deps":[{"name":"itoa","req":"^1","optional":true}],"features":{"std":[]},"features2":{"itoa":["dep:itoa"]},"yanked":false}
+"#;
+
+    const ITOA: &str = r#"{"name":"itoa","vers":"1.0.0","deps":[],"features":{},"yanked":false}"#;
+
+    fn release(id: u32, num: &str, yanked: bool) -> Release {
+        Release {
+            id,
+            num: num.to_owned(),
+            yanked,
+            created_at: String::new(),
+            downloads: 0,
+            features: BTreeMap::default(),
+            dependencies: SichtCell::default(),
+        }
+    }
+
+    /// A checkout holding the files of `serde` and `itoa`.
+    fn checkout() -> Scratch {
+        let dir = scratch("index");
+        for (name, contents) in [("serde", SERDE), ("itoa", ITOA)] {
+            let path = dir.join(IndexSource::index_path(name).unwrap());
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn index_paths_follow_the_name_length() {
+        let paths = ["a", "ab", "abc", "Serde"].map(|name| IndexSource::index_path(name).unwrap());
+        assert_eq!(paths, ["1/a", "2/ab", "3/a/abc", "se/rd/serde"]);
+        assert!(IndexSource::index_path("").is_err());
+        assert!(IndexSource::index_path("\u{e9}t\u{e9}").is_err());
+    }
+
+    #[test]
+    fn parse_file_skips_blank_lines_and_merges_features2() -> Result<()> {
+        let releases = IndexRelease::parse_file(SERDE)?;
+        assert_eq!(releases.len(), 3);
+        let features = releases[2].features();
+        assert_eq!(features.keys().collect::<Vec<_>>(), ["itoa", "std"]);
+        assert_eq!(features["itoa"], ["dep:itoa"]);
+        assert!(IndexRelease::parse_file("{\"name\":\"serde\"}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn apply_index_adds_releases_and_flips_yanks() -> Result<()> {
+        let dir = checkout();
+        let serde = Crate::with_releases(
+            Kiste::indexed(1, "serde".to_owned()),
+            [release(10, "0.9.0", true), release(11, "1.0.0", false)],
+        );
+        let map = [(1, "serde".to_owned(), serde)]
+            .into_iter()
+            .collect::<SichtMap<u32, String, Crate>>();
+        let carriage = Carriage::from_map(map);
+
+        let source = IndexSource::Checkout(dir.to_path_buf());
+        let delta = carriage.apply_index(&source, &[], None, &[])?;
+        assert_eq!(
+            (delta.crates, delta.releases, delta.yanked, delta.unyanked),
+            (1, 2, 1, 1)
+        );
+
+        let map = carriage.map.borrow();
+        let serde = map.get_with_base_key(&1).unwrap();
+        assert!(!serde.release(&"0.9.0".to_owned()).unwrap().yanked);
+        assert!(serde.release(&"1.0.0".to_owned()).unwrap().yanked);
+        let added = serde.release(&"1.1.0".to_owned()).unwrap();
+        assert_eq!(added.features.keys().collect::<Vec<_>>(), ["itoa", "std"]);
+        let itoa = *carriage
+            .lookup
+            .borrow()
+            .crate_in_registry(None, "itoa")
+            .unwrap();
+        let dependencies = added.dependencies.borrow();
+        assert_eq!(dependencies[0].dependency, itoa);
+        assert_eq!(dependencies[0].version.as_deref(), Some("1.0.0"));
+        Ok(())
+    }
+
+    #[test]
+    fn apply_index_fetches_names_as_published() -> Result<()> {
+        let dir = scratch("published");
+        let path = dir.join(IndexSource::index_path("serde_json")?);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(
+            path,
+            r#"{"name":"serde_json","vers":"1.0.1","deps":[],"features":{},"yanked":false}"#,
+        )?;
+        let serde_json = Crate::with_releases(
+            Kiste::indexed(1, "serde_json".to_owned()),
+            [release(10, "1.0.0", false)],
+        );
+        let map = [(1, "serde_json".to_owned(), serde_json)]
+            .into_iter()
+            .collect::<SichtMap<u32, String, Crate>>();
+        let carriage = Carriage::from_map(map);
+
+        let source = IndexSource::Checkout(dir.to_path_buf());
+        let delta = carriage.apply_index(&source, &["Serde-JSON".to_owned()], None, &[])?;
+
+        assert_eq!(delta.releases, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn apply_index_wants_names_for_a_mirror() {
+        let carriage = Carriage::from_map(SichtMap::default());
+        // Nothing listens there, the names are asked for before anything is fetched.
+        let source = IndexSource::new("http://127.0.0.1:9/index/");
+
+        let error = carriage.apply_index(&source, &[], None, &[]).unwrap_err();
+        assert!(error.to_string().contains("name the crates"), "{error}");
+    }
+}
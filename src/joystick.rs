@@ -1,101 +1,286 @@
-use crate::carriage::Carriage;
-use crate::conditions::WhereClause;
-use crate::store::UnrolledCrate;
+use crate::conditions::PredicateComposition;
+use crate::horn::{QueryError, Span, suggest};
+use crate::platform::Platform;
+use crate::rack::{Rack, Route};
+use crate::store::{DependencyKind, UnrolledCrate};
 use anyhow::Result;
+use chrono::NaiveDate;
 use semver::VersionReq;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::{error::Error, fmt::Display};
 
 #[derive(Clone, Debug, Default)]
 pub struct Query {
     package: String,
-    conditions: Option<WhereClause>,
+    conditions: Option<PredicateComposition>,
+    reverse: bool,
+    depth: Option<usize>,
+    features: Vec<String>,
+    no_default_features: bool,
+    target: Option<Platform>,
 }
 
 impl Query {
-    pub fn apply_to_carriage<'a>(
-        &'a self,
-        carriage: &'a mut Carriage,
-    ) -> Result<Option<UnrolledCrate>> {
-        match self.conditions {
-            None => Ok(carriage.search(&self.package)),
-            _ => todo!(),
+    /// Parses a query such as `LIFT serde WHERE kind = normal DEPTH 2`.
+    ///
+    /// # Errors
+    ///
+    /// When the query doesn't parse, the [`QueryError`] points at the part that's wrong.
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        QueryAccumulator::from_input(input)?.try_into()
+    }
+
+    /// The query lifting `package` with nothing else set, the builder methods below add to it.
+    #[must_use]
+    pub fn lift(package: &str) -> Self {
+        Self {
+            package: package.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// Restricts the tree with the conditions a `WHERE` takes, e.g.
+    /// `kind = normal AND optional = false`.
+    ///
+    /// # Errors
+    ///
+    /// When the conditions are blank or don't parse, with spans into `conditions`.
+    pub fn filter(mut self, conditions: &str) -> Result<Self, QueryError> {
+        if conditions.trim().is_empty() {
+            return Err(QueryError::NoConditions {
+                span: Span::end_of(conditions),
+            });
+        }
+        let tokens = Panel::TokenValue(conditions.split_whitespace().collect());
+        self.conditions = Some(PredicateComposition::try_from_tokens(
+            &tokens,
+            &conditions[..0],
+            conditions,
+        )?);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn reverse(mut self) -> Self {
+        self.reverse = true;
+        self
+    }
+
+    #[must_use]
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    #[must_use]
+    pub fn features<I: IntoIterator<Item = S>, S: Into<String>>(mut self, features: I) -> Self {
+        self.features = features.into_iter().map(Into::into).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn no_default_features(mut self) -> Self {
+        self.no_default_features = true;
+        self
+    }
+
+    #[must_use]
+    pub fn target(mut self, target: Platform) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub(crate) fn apply_to_rack(&self, rack: &Rack) -> Option<UnrolledCrate> {
+        let route = self.route();
+        if self.reverse {
+            rack.search_reverse(&self.package, &route)
+        } else {
+            rack.search(&self.package, &route)
+        }
+    }
+
+    pub(crate) fn route(&self) -> Route<'_> {
+        Route {
+            conditions: self.conditions.as_ref(),
+            depth: self.depth,
+            features: &self.features,
+            no_default_features: self.no_default_features,
+            target: self.target,
+            pins: None,
         }
     }
 }
 #[derive(Default, Debug)]
-pub struct QueryAccumulator<'a>(HashMap<Button, Panel<'a>>);
+pub struct QueryAccumulator<'a> {
+    input: &'a str,
+    panels: HashMap<Button, Panel<'a>>,
+    /// The keyword tokens as written, to point errors at them.
+    keywords: HashMap<Button, &'a str>,
+}
 
 impl<'a> QueryAccumulator<'a> {
-    pub fn from_input(input: &'a str) -> Self {
-        let (mut chars, Some(button), mut collector) = input.split_whitespace().fold(
-            (HashMap::<Button, Panel<'a>>::new(), None, vec![]),
-            |(mut acc, button, mut collector), token| match (Panel::try_from_keyword(token), button)
-            {
-                (Some(Panel::Button(kw)), Some(but)) if !collector.is_empty() => {
-                    let col = core::mem::take(&mut collector);
-                    acc.insert(but, Panel::TokenValue(col));
-                    (acc, Some(kw), vec![])
+    pub fn from_input(input: &'a str) -> Result<Self, QueryError> {
+        let mut accumulator = Self {
+            input,
+            ..Self::default()
+        };
+        let mut button = None;
+        let mut collector = vec![];
+        for token in input.split_whitespace() {
+            match (Button::try_from_keyword(token), button) {
+                (Some(kw), _) if accumulator.keywords.contains_key(&kw) => {
+                    return Err(QueryError::RepeatedKeyword {
+                        keyword: token.to_owned(),
+                        span: Span::of(input, token),
+                    });
                 }
-                (Some(Panel::Button(kw)), _) => (acc, Some(kw), collector),
-                (None, _) => {
-                    collector.push(token);
-                    (acc, button, collector)
+                (Some(kw), previous) => {
+                    if let Some(previous) = previous {
+                        let col = core::mem::take(&mut collector);
+                        accumulator.panels.insert(previous, Panel::TokenValue(col));
+                    }
+                    accumulator.keywords.insert(kw, token);
+                    button = Some(kw);
                 }
-                _ => unreachable!(),
-            },
-        ) else {
-            todo!()
-        };
+                (None, Some(_)) => collector.push(token),
+                (None, None) => {
+                    return Err(QueryError::ExpectedKeyword {
+                        found: token.to_owned(),
+                        span: Span::of(input, token),
+                        suggestion: suggest(token, Button::KEYWORDS),
+                    });
+                }
+            }
+        }
+
+        if let Some(button) = button {
+            accumulator
+                .panels
+                .insert(button, Panel::TokenValue(collector));
+        }
+        Ok(accumulator)
+    }
 
-        if !collector.is_empty() {
-            let col = core::mem::take(&mut collector);
-            chars.insert(button, Panel::TokenValue(col));
+    pub fn get(&self, key: Button) -> Option<&Panel<'a>> {
+        self.panels.get(&key)
+    }
+
+    fn keyword(&self, key: Button) -> &'a str {
+        self.keywords.get(&key).copied().unwrap_or_default()
+    }
+
+    /// The tokens of `key`, `None` if the keyword wasn't given.
+    fn tokens(&self, key: Button) -> Option<&[&'a str]> {
+        match self.get(key)? {
+            Panel::TokenValue(tokens) => Some(tokens),
+            Panel::Button(_) => None,
         }
+    }
 
-        QueryAccumulator(chars)
+    /// The single token `key` takes, which has to be there when the keyword is.
+    fn single(&self, key: Button, expected: &'static str) -> Result<Option<&'a str>, QueryError> {
+        match self.tokens(key) {
+            None => Ok(None),
+            Some([token]) => Ok(Some(token)),
+            Some([]) => Err(self.missing(key, expected)),
+            Some([_, extra, ..]) => Err(self.unexpected(extra, expected)),
+        }
     }
 
-    pub fn try_get(&self, key: Button) -> Result<&Panel<'a>, InvalidQueryError> {
-        if let Some(entry) = self.0.get(&key) {
-            Ok(entry)
-        } else {
-            Err(InvalidQueryError {})
+    /// Keywords like `REVERSE` are flags and take no tokens.
+    fn flag(&self, key: Button) -> Result<bool, QueryError> {
+        match self.tokens(key) {
+            None => Ok(false),
+            Some([]) => Ok(true),
+            Some([extra, ..]) => Err(self.unexpected(extra, "a keyword")),
+        }
+    }
+
+    fn missing(&self, key: Button, expected: &'static str) -> QueryError {
+        let keyword = self.keyword(key);
+        QueryError::MissingValue {
+            expected,
+            after: keyword.to_owned(),
+            span: Span::after(self.input, keyword),
+        }
+    }
+
+    /// A token left over after what a keyword takes is most often a misspelled keyword.
+    fn unexpected(&self, token: &str, expected: &'static str) -> QueryError {
+        QueryError::UnexpectedToken {
+            expected,
+            found: token.to_owned(),
+            span: Span::of(self.input, token),
+            suggestion: suggest(token, Button::KEYWORDS),
+        }
+    }
+
+    fn invalid(&self, token: &str, expected: &'static str) -> QueryError {
+        QueryError::InvalidValue {
+            expected,
+            found: token.to_owned(),
+            span: Span::of(self.input, token),
         }
     }
 }
 
 impl TryFrom<QueryAccumulator<'_>> for Query {
-    type Error = InvalidQueryError;
-    fn try_from(accumulator: QueryAccumulator<'_>) -> Result<Self, InvalidQueryError> {
-        let Some(Panel::TokenValue(krate)) = accumulator.0.get(&Button::Lift) else {
-            return Err(InvalidQueryError {});
+    type Error = QueryError;
+    fn try_from(accumulator: QueryAccumulator<'_>) -> Result<Self, QueryError> {
+        let Some(package) = accumulator.single(Button::Lift, "a crate")? else {
+            return Err(QueryError::NoLift {
+                span: Span::end_of(accumulator.input),
+            });
         };
 
         let conditions = accumulator
-            .try_get(Button::Where)
-            .map(|clauses| WhereClause::try_from_tokens(clauses).unwrap())
-            .ok();
-
-        if krate.len() == 1 {
-            Ok(Self {
-                package: krate[0].into(),
-                conditions,
+            .get(Button::Where)
+            .map(|clauses| {
+                PredicateComposition::try_from_tokens(
+                    clauses,
+                    accumulator.keyword(Button::Where),
+                    accumulator.input,
+                )
             })
-        } else {
-            todo!()
-        }
-    }
-}
+            .transpose()?;
 
-#[derive(Clone, Debug)]
-pub struct InvalidQueryError {}
+        let depth = accumulator
+            .single(Button::Depth, "a number")?
+            .map(|depth| {
+                depth
+                    .parse()
+                    .map_err(|_| accumulator.invalid(depth, "a number"))
+            })
+            .transpose()?;
 
-impl Error for InvalidQueryError {}
+        let features = match accumulator.tokens(Button::Features) {
+            Some([]) => return Err(accumulator.missing(Button::Features, "a feature")),
+            Some(features) => features
+                .iter()
+                .flat_map(|token| token.split(','))
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => Vec::default(),
+        };
+
+        let target = accumulator
+            .single(Button::Target, "a target triple")?
+            .map(|triple| {
+                Platform::try_from_triple(triple)
+                    .ok_or_else(|| accumulator.invalid(triple, "a known target triple"))
+            })
+            .transpose()?;
 
-impl Display for InvalidQueryError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "InvalidQueryError")
+        Ok(Self {
+            package: package.into(),
+            conditions,
+            reverse: accumulator.flag(Button::Reverse)?,
+            depth,
+            features,
+            no_default_features: accumulator.flag(Button::NoDefaultFeatures)?,
+            target,
+        })
     }
 }
 
@@ -105,23 +290,37 @@ pub enum Panel<'a> {
     TokenValue(Vec<&'a str>),
 }
 
-impl Panel<'_> {
-    fn try_from_keyword(keyword: &str) -> Option<Self> {
-        Button::try_from_keyword(keyword).map(Self::Button)
-    }
-}
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Button {
     Lift,
     Where,
+    Reverse,
+    Depth,
+    Features,
+    NoDefaultFeatures,
+    Target,
 }
 
 impl Button {
+    pub const KEYWORDS: &[&'static str] = &[
+        "LIFT",
+        "WHERE",
+        "REVERSE",
+        "DEPTH",
+        "FEATURES",
+        "NO-DEFAULT-FEATURES",
+        "TARGET",
+    ];
+
     fn try_from_keyword(keyword: &str) -> Option<Self> {
         match keyword {
             "lift" | "LIFT" => Some(Button::Lift),
             "where" | "WHERE" => Some(Button::Where),
+            "reverse" | "REVERSE" => Some(Button::Reverse),
+            "depth" | "DEPTH" => Some(Button::Depth),
+            "features" | "FEATURES" => Some(Button::Features),
+            "no-default-features" | "NO-DEFAULT-FEATURES" => Some(Button::NoDefaultFeatures),
+            "target" | "TARGET" => Some(Button::Target),
             _ => None,
         }
     }
@@ -131,14 +330,141 @@ impl Button {
 pub enum PanelValue {
     Crate(String),
     Semver(VersionReq),
+    Number(u64),
+    Date(NaiveDate),
+    Kind(DependencyKind),
+    List(Vec<Self>),
+    Range(Box<Self>, Box<Self>),
+    Bool(bool),
 }
 
 impl PanelValue {
-    pub fn try_from_token(token: &str) -> Self {
-        if let Ok(constraint) = VersionReq::parse(token) {
-            Self::Semver(constraint)
-        } else {
-            Self::Crate(token.to_string())
+    /// Orders two values of the same kind, `None` if they can't be compared.
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Crate(left), Self::Crate(right)) => Some(left.cmp(right)),
+            (Self::Number(left), Self::Number(right)) => Some(left.cmp(right)),
+            (Self::Date(left), Self::Date(right)) => Some(left.cmp(right)),
+            (Self::Kind(left), Self::Kind(right)) => Some(left.cmp(right)),
+            (Self::Bool(left), Self::Bool(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> QueryError {
+        Query::parse(input).expect_err(input)
+    }
+
+    #[test]
+    fn malformed_queries_say_what_is_wrong_and_where() {
+        let cases = [
+            ("DEPTH 2", "expected `LIFT <crate>` in the query"),
+            (
+                "LFIT serde",
+                "expected a keyword such as LIFT, found `LFIT` at column 1, did you mean `LIFT`?",
+            ),
+            ("LIFT", "expected a crate after `LIFT` at column 5"),
+            (
+                "LIFT serde tokio",
+                "expected a crate, found `tokio` at column 12",
+            ),
+            (
+                "LIFT serde DEPTH many",
+                "`many` is not a number at column 18",
+            ),
+            (
+                "LIFT serde DEPTH 2 REVERES",
+                "expected a number, found `REVERES` at column 20, did you mean `REVERSE`?",
+            ),
+            (
+                "LIFT serde REVERSE now",
+                "expected a keyword, found `now` at column 20",
+            ),
+            (
+                "LIFT serde FEATURES",
+                "expected a feature after `FEATURES` at column 20",
+            ),
+            (
+                "LIFT serde TARGET mars",
+                "`mars` is not a known target triple at column 19",
+            ),
+            (
+                "LIFT serde WHERE",
+                "expected a condition after `WHERE` at column 17",
+            ),
+            (
+                "LIFT serde WHERE version",
+                "expected a version requirement after `version` at column 25",
+            ),
+            (
+                "LIFT serde WHERE kynd = normal",
+                "expected a condition, found `kynd` at column 18, did you mean `kind`?",
+            ),
+            (
+                "LIFT serde WHERE kind > normal",
+                "`kind` can't be compared with `>` at column 23",
+            ),
+            (
+                "LIFT serde WHERE downloads > many",
+                "`many` is not a number at column 30",
+            ),
+            (
+                "LIFT serde WHERE kind = normal ANDD optional = false",
+                "expected AND, OR or the end of the conditions, found `ANDD` at column 32, \
+                 did you mean `AND`?",
+            ),
+            (
+                "LIFT serde WHERE (kind = normal",
+                "expected `)` after `normal` at column 32",
+            ),
+            (
+                "LIFT serde WHERE name IN ()",
+                "expected at least one item after `(` at column 26",
+            ),
+            (
+                "LIFT serde WHERE downloads BETWEEN 1 TO 5",
+                "expected AND, found `TO` at column 38",
+            ),
+        ];
+        for (input, message) in cases {
+            assert_eq!(error(input).to_string(), message, "{input}");
+        }
+    }
+
+    #[test]
+    fn keywords_are_only_given_once() {
+        assert_eq!(
+            error("LIFT serde DEPTH 1 depth 2"),
+            QueryError::RepeatedKeyword {
+                keyword: "depth".to_owned(),
+                span: Span {
+                    start: 19,
+                    end: 24,
+                    chars: 19,
+                },
+            }
+        );
+        assert_eq!(
+            error("LIFT serde lift tokio").to_string(),
+            "`lift` given twice at column 12"
+        );
+    }
+
+    #[test]
+    fn blank_filters_are_refused() {
+        for conditions in ["", "   "] {
+            let error = Query::lift("serde").filter(conditions).expect_err(conditions);
+            assert_eq!(
+                error.to_string(),
+                "expected a condition to filter by",
+                "{conditions:?}"
+            );
         }
+        assert!(Query::lift("serde").filter("kind = dev").is_ok());
     }
 }
@@ -0,0 +1,61 @@
+//! forklift turns the crates.io database dump into a dependency graph and answers queries about
+//! it, the way the `forklift` binary does on the command line.
+//!
+//! An [`Engine`] holds the graph, loaded from the dump or the cache next to it. Queries are
+//! parsed from the query language or built up with [`Query::lift`], and come back as a tree of
+//! [`UnrolledCrate`]s. Once loaded the graph is frozen, which makes an `Engine` `Send + Sync`:
+//! one loaded dump can answer queries from any number of threads.
+//!
+//! ```no_run
+//! use forklift::{Config, Engine, Query};
+//!
+//! let engine = Engine::load(Config::default())?;
+//!
+//! let parsed = Query::parse("LIFT serde WHERE kind = normal DEPTH 2")?;
+//! let built = Query::lift("serde").filter("kind = normal")?.depth(2);
+//!
+//! for query in [parsed, built] {
+//!     if let Some(tree) = engine.query(&query) {
+//!         println!("{} {:?}, {} dependencies", tree.name, tree.version, tree.dependents.len());
+//!     }
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+#![deny(rust_2018_idioms)]
+#![deny(clippy::pedantic, clippy::dbg_macro)]
+#![feature(associated_type_defaults)]
+
+mod bale;
+mod carriage;
+mod cell;
+mod conditions;
+mod conveyor;
+mod crusher;
+mod dashboard;
+mod download;
+mod features;
+#[cfg(test)]
+mod fixture;
+mod fs;
+mod horn;
+mod index;
+mod joystick;
+mod lookup;
+mod manifest;
+mod platform;
+mod rack;
+mod registry;
+mod serproxy;
+mod store;
+mod tally;
+mod waybill;
+
+pub use crate::crusher::Lager;
+pub use crate::dashboard::Format;
+pub use crate::download::{Config, DUMP_URL, Engine};
+pub use crate::horn::{QueryError, Span};
+pub use crate::index::IndexDelta;
+pub use crate::joystick::Query;
+pub use crate::platform::Platform;
+pub use crate::registry::Registry;
+pub use crate::store::{DependencyKind, Marker, UnrolledCrate};
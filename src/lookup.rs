@@ -1,50 +1,78 @@
+use crate::store::Kiste;
 use serde::{Deserialize, Serialize};
-use sicht::Diplopie;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A crate name the way crates.io compares them: case doesn't matter and neither does `-`
+/// against `_`, so `Serde-JSON` is `serde_json`.
+pub fn canonical(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// Where a crate sits among the outer keys of the map: its canonical name, behind the name of
+/// its registry unless that's crates.io. Like the `Lookup`, `Serde-JSON` and `serde_json` of
+/// one registry are the same crate.
+pub fn crate_key(registry: Option<&str>, name: &str) -> String {
+    match registry {
+        Some(registry) => format!("{registry}/{}", canonical(name)),
+        None => canonical(name),
+    }
+}
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Lookup {
-    dependency: BTreeMap<u32, u32>,
+    /// Crate names as published. Like `names` this is rebuilt from the crates rather than cached.
     #[serde(skip)]
-    pub krate: Diplopie<u32, String>,
+    pub krate: BTreeMap<u32, String>,
+    /// Crate ids by registry, crates.io being `None` and therefore first, then by canonical name.
+    #[serde(skip)]
+    pub names: BTreeMap<Option<String>, HashMap<String, u32>>,
     pub dependency_version: BTreeMap<u32, u32>,
+    #[serde(default)]
+    pub dependents: BTreeMap<u32, BTreeSet<u32>>,
 }
 
 impl Lookup {
-    pub fn with_krate(krate: Diplopie<u32, String>) -> Self {
-        Self {
-            dependency: BTreeMap::default(),
-            krate,
-            dependency_version: BTreeMap::default(),
-        }
-    }
-
-    pub fn insert_dependency(&mut self, crate_id: u32, dependency: u32) {
-        self.dependency.insert(crate_id, dependency);
-    }
-
-    pub fn seed_dependencies(&mut self, dependencies: BTreeMap<u32, u32>) {
-        self.dependency = dependencies;
+    pub fn with_krate<'k, I: IntoIterator<Item = &'k Kiste>>(krates: I) -> Self {
+        let mut lookup = Self::default();
+        lookup.seed_krates(krates);
+        lookup
     }
 
-    pub fn get_dependency(&self, crate_id: u32) -> Option<&u32> {
-        self.dependency.get(&crate_id)
+    /// Replaces the names with those of `krates`, which have to be all the crates of the graph.
+    pub fn seed_krates<'k, I: IntoIterator<Item = &'k Kiste>>(&mut self, krates: I) {
+        self.krate.clear();
+        self.names.clear();
+        krates
+            .into_iter()
+            .for_each(|krate| self.insert_krate(krate));
     }
 
-    #[allow(clippy::redundant_closure_for_method_calls)]
-    pub fn get_crate_name(&self, crate_id: u32) -> Option<&String> {
-        self.krate.get(&crate_id)
+    /// Registers a crate added to the graph. Should two names of a registry be the same once
+    /// canonical, the first one keeps it.
+    pub fn insert_krate(&mut self, krate: &Kiste) {
+        self.krate.insert(krate.id, krate.name.clone());
+        self.names
+            .entry(krate.registry.clone())
+            .or_default()
+            .entry(canonical(&krate.name))
+            .or_insert(krate.id);
     }
 
-    pub fn get_crate_id(&self, crate_name: &String) -> Option<&u32> {
-        self.krate.get_urbild(crate_name)
+    pub fn crate_in_registry(&self, registry: Option<&str>, crate_name: &str) -> Option<&u32> {
+        self.names
+            .get(&registry.map(str::to_owned))?
+            .get(&canonical(crate_name))
     }
 
     pub fn insert_dependency_relation(&mut self, version_id: u32, crate_id: u32) {
         self.dependency_version.insert(version_id, crate_id);
     }
 
-    pub fn get_dependency_relation_for_version(&self, version_id: u32) -> Option<&u32> {
-        self.dependency_version.get(&version_id)
+    /// Records that the release `version_id` depends on `crate_id`, for reverse traversal.
+    pub fn insert_dependent(&mut self, crate_id: u32, version_id: u32) {
+        self.dependents
+            .entry(crate_id)
+            .or_default()
+            .insert(version_id);
     }
 }
@@ -0,0 +1,644 @@
+use crate::features::Activation;
+use crate::rack::{Rack, Route, Trip};
+use crate::store::{DependencyKind, Marker, Skid, UnrolledCrate};
+use anyhow::{Context, Result, bail};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Locked versions of registry packages by name, highest first.
+pub type Pins = BTreeMap<String, Vec<(Version, String)>>;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    package: Option<ManifestPackage>,
+    workspace: Option<ManifestWorkspace>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+    #[serde(flatten)]
+    tables: DependencyTables,
+    #[serde(default)]
+    target: BTreeMap<String, DependencyTables>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestPackage {
+    name: String,
+    version: Option<toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestWorkspace {
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, ManifestDependency>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DependencyTables {
+    #[serde(default)]
+    dependencies: BTreeMap<String, ManifestDependency>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, ManifestDependency>,
+    #[serde(default)]
+    build_dependencies: BTreeMap<String, ManifestDependency>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum ManifestDependency {
+    Simple(String),
+    Detailed(DetailedDependency),
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DetailedDependency {
+    version: Option<String>,
+    package: Option<String>,
+    path: Option<PathBuf>,
+    git: Option<String>,
+    /// The name of an alternate registry from `.cargo/config.toml`.
+    registry: Option<String>,
+    #[serde(default)]
+    optional: bool,
+    default_features: Option<bool>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    workspace: bool,
+}
+
+impl ManifestDependency {
+    fn detailed(&self) -> DetailedDependency {
+        match self {
+            ManifestDependency::Simple(version) => DetailedDependency {
+                version: Some(version.clone()),
+                ..DetailedDependency::default()
+            },
+            ManifestDependency::Detailed(detailed) => detailed.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    fn name(&self, path: &Path) -> String {
+        self.package.as_ref().map_or_else(
+            || {
+                path.parent()
+                    .and_then(Path::file_name)
+                    .map_or_else(|| "workspace".to_owned(), |n| n.to_string_lossy().into())
+            },
+            |package| package.name.clone(),
+        )
+    }
+
+    /// `version.workspace = true` and similar inherited versions are left out.
+    fn version(&self) -> Option<String> {
+        match self.package.as_ref()?.version.as_ref()? {
+            toml::Value::String(version) => Some(version.clone()),
+            _ => None,
+        }
+    }
+
+    /// Every dependency of the package together with its kind and `[target]` condition.
+    fn dependencies(&self) -> Vec<(&String, &ManifestDependency, DependencyKind, Option<&str>)> {
+        let targeted = self
+            .target
+            .iter()
+            .map(|(target, tables)| (tables, Some(target.as_str())));
+        std::iter::once((&self.tables, None))
+            .chain(targeted)
+            .flat_map(|(tables, target)| {
+                [
+                    (&tables.dependencies, DependencyKind::Normal),
+                    (&tables.build_dependencies, DependencyKind::Build),
+                    (&tables.dev_dependencies, DependencyKind::Dev),
+                ]
+                .into_iter()
+                .flat_map(move |(table, kind)| {
+                    table
+                        .iter()
+                        .map(move |(name, dependency)| (name, dependency, kind, target))
+                })
+            })
+            .collect()
+    }
+}
+
+/// A local workspace unrolled against the registry data in a `Rack`. Registry packages
+/// take the versions locked in `Cargo.lock` wherever the lockfile has one.
+pub struct Workspace {
+    manifest_path: PathBuf,
+    /// The directory of the manifest with the `[workspace]` table, paths in
+    /// `[workspace.dependencies]` are relative to it.
+    root: PathBuf,
+    inherited: BTreeMap<String, ManifestDependency>,
+    /// The manifests, canonicalized, whose dev-dependencies are unrolled: the one opened and
+    /// the members of its workspace. Other path dependencies are built like registry ones.
+    members: BTreeSet<PathBuf>,
+    pins: Pins,
+}
+
+impl Workspace {
+    /// Opens the package or workspace at `manifest_path`. A member manifest works too, what it
+    /// inherits is read from the workspace root further up.
+    pub fn open<P: AsRef<Path>>(manifest_path: P) -> Result<Self> {
+        let manifest_path = manifest_path.as_ref().to_owned();
+        let (root, inherited, members) = match Self::find_root(&manifest_path) {
+            Some((root, workspace)) => {
+                let mut members = Self::members(&root, &workspace.members);
+                members.push(root.join("Cargo.toml"));
+                (root, workspace.dependencies, members)
+            }
+            None => (
+                Self::dir(&manifest_path).to_owned(),
+                BTreeMap::default(),
+                Vec::default(),
+            ),
+        };
+        let members = members
+            .iter()
+            .chain([&manifest_path])
+            .filter_map(|path| path.canonicalize().ok())
+            .collect();
+        let pins = Self::find_lockfile(&manifest_path)
+            .map(|path| Self::load_pins(&path))
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            manifest_path,
+            root,
+            inherited,
+            members,
+            pins,
+        })
+    }
+
+    fn dir(manifest_path: &Path) -> &Path {
+        manifest_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// The nearest manifest from `manifest_path` upwards with a `[workspace]` table, the way
+    /// Cargo looks for it.
+    fn find_root(manifest_path: &Path) -> Option<(PathBuf, ManifestWorkspace)> {
+        Self::dir(manifest_path).ancestors().find_map(|dir| {
+            let path = dir.join("Cargo.toml");
+            if !path.is_file() {
+                return None;
+            }
+            let workspace = Manifest::load(&path).ok()?.workspace?;
+            Some((dir.to_owned(), workspace))
+        })
+    }
+
+    fn find_lockfile(manifest_path: &Path) -> Option<PathBuf> {
+        manifest_path
+            .ancestors()
+            .skip(1)
+            .map(|dir| dir.join("Cargo.lock"))
+            .find(|path| path.is_file())
+    }
+
+    fn load_pins(path: &Path) -> Result<Pins> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let lockfile: Lockfile =
+            toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+
+        let mut pins = lockfile
+            .package
+            .into_iter()
+            .filter(|package| {
+                package
+                    .source
+                    .as_deref()
+                    .is_some_and(|source| {
+                        source.starts_with("registry+") || source.starts_with("sparse+")
+                    })
+            })
+            .filter_map(|package| {
+                let version = Version::parse(&package.version).ok()?;
+                Some((package.name, (version, package.version)))
+            })
+            .fold(Pins::new(), |mut pins, (name, pin)| {
+                pins.entry(name).or_default().push(pin);
+                pins
+            });
+        for versions in pins.values_mut() {
+            versions.sort_unstable_by(|(l, _), (r, _)| r.cmp(l));
+        }
+        Ok(pins)
+    }
+
+    pub fn pins(&self) -> &Pins {
+        &self.pins
+    }
+
+    pub fn unroll(&self, rack: &Rack, route: &Route<'_>) -> Result<UnrolledCrate> {
+        let mut visited = BTreeSet::new();
+        let mut trip = Trip::default();
+        self.unroll_manifest(&self.manifest_path, rack, route, &mut visited, &mut trip)
+    }
+
+    fn unroll_manifest(
+        &self,
+        manifest_path: &Path,
+        rack: &Rack,
+        route: &Route<'_>,
+        visited: &mut BTreeSet<PathBuf>,
+        trip: &mut Trip,
+    ) -> Result<UnrolledCrate> {
+        let manifest = Manifest::load(manifest_path)?;
+        let dir = Self::dir(manifest_path);
+        let canonical = manifest_path.canonicalize()?;
+        let member = self.members.contains(&canonical);
+        visited.insert(canonical);
+
+        let activation = Activation::resolve(
+            &manifest.features,
+            route.features.iter().cloned(),
+            !route.no_default_features,
+        );
+        let mut dependents = manifest
+            .dependencies()
+            .into_iter()
+            .filter(|(_, _, kind, _)| member || *kind != DependencyKind::Dev)
+            .map(|(name, dependency, kind, target)| {
+                self.unroll_dependency(
+                    dir,
+                    name,
+                    dependency,
+                    kind,
+                    target,
+                    &activation,
+                    rack,
+                    route,
+                    visited,
+                    trip,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let members = manifest
+            .workspace
+            .as_ref()
+            .map(|workspace| Self::members(dir, &workspace.members))
+            .unwrap_or_default();
+        for member in members {
+            if !visited.contains(&member.canonicalize()?) {
+                dependents.push(self.unroll_manifest(&member, rack, route, visited, trip)?);
+            }
+        }
+
+        Ok(UnrolledCrate {
+            name: manifest.name(manifest_path),
+            version: manifest.version(),
+            features: activation.enabled(),
+            dependents,
+            ..UnrolledCrate::default()
+        })
+    }
+
+    /// Expands `members` entries, supporting the common `crates/*` form of globs.
+    fn members(dir: &Path, members: &[String]) -> Vec<PathBuf> {
+        members
+            .iter()
+            .flat_map(|member| match member.strip_suffix("/*") {
+                Some(parent) => fs::read_dir(dir.join(parent))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path().join("Cargo.toml"))
+                    .collect::<Vec<_>>(),
+                None => vec![dir.join(member).join("Cargo.toml")],
+            })
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn unroll_dependency(
+        &self,
+        dir: &Path,
+        name: &str,
+        dependency: &ManifestDependency,
+        kind: DependencyKind,
+        target: Option<&str>,
+        activation: &Activation,
+        rack: &Rack,
+        route: &Route<'_>,
+        visited: &mut BTreeSet<PathBuf>,
+        trip: &mut Trip,
+    ) -> Result<Option<UnrolledCrate>> {
+        let mut detailed = dependency.detailed();
+        let mut dir = dir;
+        if detailed.workspace {
+            let Some(inherited) = self.inherited.get(name) else {
+                bail!("{name} is inherited but the workspace doesn't declare it");
+            };
+            let DetailedDependency {
+                optional,
+                default_features,
+                features,
+                ..
+            } = detailed;
+            detailed = inherited.detailed();
+            detailed.optional |= optional;
+            // Like Cargo, a member can switch the default features back on but not off.
+            if default_features == Some(true) {
+                detailed.default_features = Some(true);
+            }
+            detailed.features.extend(features);
+            dir = &self.root;
+        }
+
+        if let Some(path) = &detailed.path {
+            // Switched off and filtered out the way registry edges are.
+            let edge = Skid {
+                optional: detailed.optional,
+                target: target.map(str::to_owned),
+                ..Skid::new_with_dependency(0, String::new(), kind)
+            };
+            if !activation.enables(name, &edge) || !route.reaches(&edge) {
+                return Ok(None);
+            }
+
+            let manifest_path = dir.join(path).join("Cargo.toml");
+            let canonical = manifest_path
+                .canonicalize()
+                .with_context(|| format!("finding {}", manifest_path.display()))?;
+            if visited.contains(&canonical) {
+                let manifest = Manifest::load(&manifest_path)?;
+                return Ok(Some(UnrolledCrate {
+                    name: manifest.name(&manifest_path),
+                    version: manifest.version(),
+                    marker: Some(Marker::Repeated),
+                    ..UnrolledCrate::default()
+                }));
+            }
+            return self
+                .unroll_manifest(&manifest_path, rack, route, visited, trip)
+                .map(Some);
+        }
+
+        let package = detailed.package.as_deref().unwrap_or(name);
+        let req = detailed.version.clone().unwrap_or_else(|| "*".to_owned());
+        // Git dependencies and packages the dump doesn't know about stay unresolved leaves.
+        let krate = detailed
+            .git
+            .is_none()
+            .then(|| rack.crate_in_registry(detailed.registry.as_deref(), package))
+            .flatten();
+        let Some(krate) = krate else {
+            return Ok(Some(UnrolledCrate {
+                name: package.to_owned(),
+                registry: detailed.registry.clone(),
+                req: Some(req),
+                kind: Some(kind),
+                ..UnrolledCrate::default()
+            }));
+        };
+
+        let mut skid = Skid {
+            name: Some(name.to_owned()),
+            optional: detailed.optional,
+            default_features: detailed.default_features.unwrap_or(true),
+            features: detailed.features,
+            target: target.map(str::to_owned),
+            ..Skid::new_with_dependency(krate.krate.id, req, kind)
+        };
+        skid.resolve(&krate.published_versions());
+
+        Ok(rack.generate_if_not_traversed(&skid, activation, route, 1, trip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carriage::Carriage;
+    use crate::fixture::{self, scratch};
+    use crate::platform::Platform;
+
+    fn write(path: &Path, contents: &str) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    #[test]
+    fn members_inherit_paths_relative_to_the_workspace_root() -> Result<()> {
+        let dir = scratch("workspace");
+        write(
+            &dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n\n\
+             [workspace.dependencies]\nshared = { path = \"crates/shared\" }\n",
+        )?;
+        write(
+            &dir.join("crates/app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nshared.workspace = true\n",
+        )?;
+        write(
+            &dir.join("crates/shared/Cargo.toml"),
+            "[package]\nname = \"shared\"\n",
+        )?;
+
+        let unrolled = Workspace::open(dir.join("crates/app/Cargo.toml"))
+            .and_then(|workspace| workspace.unroll(&Rack::default(), &Route::default()))?;
+        assert_eq!(unrolled.name, "app");
+        assert_eq!(
+            unrolled
+                .dependents
+                .iter()
+                .map(|dependent| dependent.name.as_str())
+                .collect::<Vec<_>>(),
+            ["shared"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn only_members_bring_their_dev_dependencies() -> Result<()> {
+        let dir = scratch("dev-members");
+        write(&dir.join("Cargo.toml"), "[workspace]\nmembers = [\"app\"]\n")?;
+        write(
+            &dir.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n\
+             [dependencies]\nlocal = { path = \"../vendor/local\" }\n\n\
+             [dev-dependencies]\ncriterion = \"0.5\"\n",
+        )?;
+        write(
+            &dir.join("vendor/local/Cargo.toml"),
+            "[package]\nname = \"local\"\n\n\
+             [dependencies]\nitoa = \"1\"\n\n\
+             [dev-dependencies]\nproptest = \"1\"\n",
+        )?;
+
+        let unrolled = Workspace::open(dir.join("Cargo.toml"))
+            .and_then(|workspace| workspace.unroll(&Rack::default(), &Route::default()))?;
+        let names = |node: &UnrolledCrate| {
+            node.dependents
+                .iter()
+                .map(|dependent| dependent.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let app = &unrolled.dependents[0];
+        assert_eq!(names(app), ["local", "criterion"]);
+        assert_eq!(names(&app.dependents[0]), ["itoa"]);
+        Ok(())
+    }
+
+    #[test]
+    fn inherited_dependencies_add_the_member_features() -> Result<()> {
+        let dir = scratch("inherited-features");
+        let dump = dir.join("db-dump.tar.gz");
+        fixture::write_dump(&dump)?;
+        let rack = Rack::from(Carriage::unarchive(&dump, true)?);
+        write(
+            &dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n\n\
+             [workspace.dependencies]\nserde = { version = \"1\", default-features = false }\n",
+        )?;
+        write(
+            &dir.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n\
+             [dependencies]\nserde = { workspace = true, features = [\"derive\"] }\n",
+        )?;
+
+        let app = Workspace::open(dir.join("app/Cargo.toml"))
+            .and_then(|workspace| workspace.unroll(&rack, &Route::default()))?;
+        let serde = &app.dependents[0];
+        assert_eq!(serde.features, ["derive"]);
+        assert_eq!(serde.dependents[0].name, "serde_derive");
+        Ok(())
+    }
+
+    #[test]
+    fn inheriting_an_undeclared_dependency_fails() -> Result<()> {
+        let dir = scratch("inherited-missing");
+        write(&dir.join("Cargo.toml"), "[workspace]\nmembers = [\"app\"]\n")?;
+        write(
+            &dir.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[dependencies]\nserde.workspace = true\n",
+        )?;
+
+        let unrolled = Workspace::open(dir.join("app/Cargo.toml"))
+            .and_then(|workspace| workspace.unroll(&Rack::default(), &Route::default()));
+        assert!(unrolled.is_err_and(|error| {
+            error.to_string() == "serde is inherited but the workspace doesn't declare it"
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn path_dependencies_are_gated_and_referenced_once_unrolled() -> Result<()> {
+        let dir = scratch("path-gates");
+        write(
+            &dir.join("Cargo.toml"),
+            "[package]\nname = \"app\"\n\n\
+             [features]\nextra = [\"dep:bonus\"]\n\n\
+             [dependencies]\nshared = { path = \"shared\" }\nwrapper = { path = \"wrapper\" }\n\
+             bonus = { path = \"bonus\", optional = true }\n\n\
+             [target.'cfg(windows)'.dependencies]\nwinapi = { path = \"winapi\" }\n",
+        )?;
+        write(
+            &dir.join("wrapper/Cargo.toml"),
+            "[package]\nname = \"wrapper\"\n\n[dependencies]\nshared = { path = \"../shared\" }\n",
+        )?;
+        for name in ["shared", "bonus", "winapi"] {
+            write(
+                &dir.join(name).join("Cargo.toml"),
+                &format!("[package]\nname = \"{name}\"\n"),
+            )?;
+        }
+        let workspace = Workspace::open(dir.join("Cargo.toml"))?;
+        let names = |node: &UnrolledCrate| {
+            node.dependents
+                .iter()
+                .map(|dependent| (dependent.name.clone(), dependent.marker))
+                .collect::<Vec<_>>()
+        };
+
+        let linux = Route {
+            target: Platform::try_from_triple("x86_64-unknown-linux-gnu"),
+            ..Route::default()
+        };
+        let app = workspace.unroll(&Rack::default(), &linux)?;
+        assert_eq!(
+            names(&app),
+            [("shared".to_owned(), None), ("wrapper".to_owned(), None)]
+        );
+        assert_eq!(
+            names(&app.dependents[1]),
+            [("shared".to_owned(), Some(Marker::Repeated))]
+        );
+
+        let extra = ["extra".to_owned()];
+        let route = Route {
+            features: &extra,
+            ..Route::default()
+        };
+        let app = workspace.unroll(&Rack::default(), &route)?;
+        let unrolled = names(&app)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(unrolled, ["bonus", "shared", "wrapper", "winapi"]);
+        Ok(())
+    }
+
+    #[test]
+    fn pins_come_from_git_and_sparse_registries() -> Result<()> {
+        let dir = scratch("pins");
+        let lockfile = dir.join("Cargo.lock");
+        write(
+            &lockfile,
+            "[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\n\
+             source = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n\
+             [[package]]\nname = \"serde\"\nversion = \"1.0.100\"\n\
+             source = \"sparse+https://index.crates.io/\"\n\n\
+             [[package]]\nname = \"local\"\nversion = \"0.1.0\"\n\n\
+             [[package]]\nname = \"forked\"\nversion = \"0.1.0\"\n\
+             source = \"git+https://example.com/forked#abc\"\n",
+        )?;
+
+        let pins = Workspace::load_pins(&lockfile)?;
+        assert_eq!(pins.keys().collect::<Vec<_>>(), ["serde"]);
+        let nums = pins["serde"]
+            .iter()
+            .map(|(_, num)| num.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(nums, ["1.0.200", "1.0.100"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// The cfg values rustc reports for a target, enough to settle the `[target.'cfg(..)']` tables
+/// crates actually publish.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Platform {
+    pub triple: &'static str,
+    arch: &'static str,
+    vendor: &'static str,
+    os: &'static str,
+    env: &'static str,
+    family: Option<&'static str>,
+    pointer_width: &'static str,
+    endian: &'static str,
+    /// The widest atomics with compare-and-swap in bits, what `target_has_atomic` goes by. Zero
+    /// on targets that only have atomic loads and stores.
+    max_atomic_width: u16,
+}
+
+impl Platform {
+    const fn new(
+        triple: &'static str,
+        arch: &'static str,
+        vendor: &'static str,
+        os: &'static str,
+        env: &'static str,
+        family: Option<&'static str>,
+        pointer_width: &'static str,
+        max_atomic_width: u16,
+    ) -> Self {
+        Self {
+            triple,
+            arch,
+            vendor,
+            os,
+            env,
+            family,
+            pointer_width,
+            endian: "little",
+            max_atomic_width,
+        }
+    }
+
+    #[must_use]
+    pub fn try_from_triple(triple: &str) -> Option<Self> {
+        PLATFORMS
+            .iter()
+            .find(|platform| platform.triple == triple)
+            .copied()
+    }
+
+    /// `target` is either a plain triple or a `cfg(..)` expression. Expressions this can't parse
+    /// keep the edge, dropping a dependency that is compiled would be worse than showing one
+    /// that isn't.
+    #[must_use]
+    pub fn matches(&self, target: &str) -> bool {
+        if target.starts_with("cfg(") {
+            CfgExpr::parse(target).is_none_or(|expr| expr.evaluate(self))
+        } else {
+            target == self.triple
+        }
+    }
+
+    /// `unix` and `windows` are the only families rustc also sets as bare names, `cfg(wasm)` is
+    /// never true, it takes `target_family = "wasm"`.
+    fn has_name(&self, name: &str) -> bool {
+        matches!(name, "unix" | "windows") && Some(name) == self.family
+    }
+
+    fn has_key_value(&self, key: &str, value: &str) -> bool {
+        match key {
+            "target_arch" => self.arch == value,
+            "target_vendor" => self.vendor == value,
+            "target_os" => self.os == value,
+            "target_env" => self.env == value,
+            "target_family" => self.family == Some(value),
+            "target_pointer_width" => self.pointer_width == value,
+            "target_endian" => self.endian == value,
+            "target_has_atomic" => {
+                let width = if value == "ptr" { self.pointer_width } else { value };
+                width
+                    .parse::<u16>()
+                    .is_ok_and(|width| (8..=self.max_atomic_width).contains(&width))
+            }
+            _ => false,
+        }
+    }
+}
+
+const UNIX: Option<&str> = Some("unix");
+const WINDOWS: Option<&str> = Some("windows");
+const WASM: Option<&str> = Some("wasm");
+
+#[rustfmt::skip]
+pub const PLATFORMS: &[Platform] = &[
+    Platform::new("x86_64-unknown-linux-gnu", "x86_64", "unknown", "linux", "gnu", UNIX, "64", 64),
+    Platform::new("x86_64-unknown-linux-musl", "x86_64", "unknown", "linux", "musl", UNIX, "64", 64),
+    Platform::new("aarch64-unknown-linux-gnu", "aarch64", "unknown", "linux", "gnu", UNIX, "64", 64),
+    Platform::new("aarch64-unknown-linux-musl", "aarch64", "unknown", "linux", "musl", UNIX, "64", 64),
+    Platform::new("i686-unknown-linux-gnu", "x86", "unknown", "linux", "gnu", UNIX, "32", 64),
+    Platform::new("armv7-unknown-linux-gnueabihf", "arm", "unknown", "linux", "gnu", UNIX, "32", 64),
+    Platform::new("x86_64-unknown-freebsd", "x86_64", "unknown", "freebsd", "", UNIX, "64", 64),
+    Platform::new("x86_64-apple-darwin", "x86_64", "apple", "macos", "", UNIX, "64", 64),
+    Platform::new("aarch64-apple-darwin", "aarch64", "apple", "macos", "", UNIX, "64", 64),
+    Platform::new("aarch64-apple-ios", "aarch64", "apple", "ios", "", UNIX, "64", 64),
+    Platform::new("aarch64-linux-android", "aarch64", "unknown", "android", "", UNIX, "64", 64),
+    Platform::new("x86_64-pc-windows-msvc", "x86_64", "pc", "windows", "msvc", WINDOWS, "64", 64),
+    Platform::new("x86_64-pc-windows-gnu", "x86_64", "pc", "windows", "gnu", WINDOWS, "64", 64),
+    Platform::new("i686-pc-windows-msvc", "x86", "pc", "windows", "msvc", WINDOWS, "32", 64),
+    Platform::new("aarch64-pc-windows-msvc", "aarch64", "pc", "windows", "msvc", WINDOWS, "64", 64),
+    Platform::new("wasm32-unknown-unknown", "wasm32", "unknown", "unknown", "", WASM, "32", 64),
+    Platform::new("wasm32-wasip1", "wasm32", "unknown", "wasi", "p1", WASM, "32", 64),
+    Platform::new("thumbv6m-none-eabi", "arm", "none", "none", "", None, "32", 0),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CfgExpr {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Self>),
+    Any(Vec<Self>),
+    Not(Box<Self>),
+}
+
+impl CfgExpr {
+    /// Parses a whole `cfg(<predicate>)`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut tokens = CfgTokens::new(input);
+        (tokens.next()? == CfgToken::Ident("cfg")).then_some(())?;
+        (tokens.next()? == CfgToken::Open).then_some(())?;
+        let expr = Self::parse_predicate(&mut tokens)?;
+        (tokens.next()? == CfgToken::Close).then_some(())?;
+        tokens.next().is_none().then_some(expr)
+    }
+
+    fn parse_predicate(tokens: &mut Peekable<CfgTokens<'_>>) -> Option<Self> {
+        let CfgToken::Ident(ident) = tokens.next()? else {
+            return None;
+        };
+
+        match (ident, tokens.peek()) {
+            ("all" | "any" | "not", Some(CfgToken::Open)) => {
+                tokens.next();
+                let list = Self::parse_list(tokens)?;
+                match ident {
+                    "all" => Some(CfgExpr::All(list)),
+                    "any" => Some(CfgExpr::Any(list)),
+                    _ => match <[Self; 1]>::try_from(list) {
+                        Ok([inner]) => Some(CfgExpr::Not(Box::new(inner))),
+                        Err(_) => None,
+                    },
+                }
+            }
+            (key, Some(CfgToken::Equals)) => {
+                tokens.next();
+                let CfgToken::Literal(value) = tokens.next()? else {
+                    return None;
+                };
+                Some(CfgExpr::KeyValue(key.to_owned(), value.to_owned()))
+            }
+            (name, _) => Some(CfgExpr::Name(name.to_owned())),
+        }
+    }
+
+    /// Parses predicates up to and including the closing parenthesis, allowing a trailing comma.
+    fn parse_list(tokens: &mut Peekable<CfgTokens<'_>>) -> Option<Vec<Self>> {
+        let mut list = Vec::new();
+        loop {
+            if tokens.peek() == Some(&CfgToken::Close) {
+                tokens.next();
+                return Some(list);
+            }
+
+            list.push(Self::parse_predicate(tokens)?);
+            match tokens.next()? {
+                CfgToken::Comma => {}
+                CfgToken::Close => return Some(list),
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn evaluate(&self, platform: &Platform) -> bool {
+        match self {
+            CfgExpr::Name(name) => platform.has_name(name),
+            CfgExpr::KeyValue(key, value) => platform.has_key_value(key, value),
+            CfgExpr::All(list) => list.iter().all(|expr| expr.evaluate(platform)),
+            CfgExpr::Any(list) => list.iter().any(|expr| expr.evaluate(platform)),
+            CfgExpr::Not(expr) => !expr.evaluate(platform),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CfgToken<'a> {
+    Ident(&'a str),
+    Literal(&'a str),
+    Open,
+    Close,
+    Comma,
+    Equals,
+}
+
+struct CfgTokens<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> CfgTokens<'a> {
+    fn new(input: &'a str) -> Peekable<Self> {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+        .peekable()
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, start: usize, accept: F) -> &'a str {
+        let mut end = start;
+        while let Some((i, c)) = self.chars.peek().copied()
+            && accept(c)
+        {
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+        &self.input[start..end]
+    }
+}
+
+impl<'a> Iterator for CfgTokens<'a> {
+    type Item = CfgToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, c) = self.chars.find(|(_, c)| !c.is_whitespace())?;
+        match c {
+            '(' => Some(CfgToken::Open),
+            ')' => Some(CfgToken::Close),
+            ',' => Some(CfgToken::Comma),
+            '=' => Some(CfgToken::Equals),
+            '"' => {
+                let literal = self.take_while(start + 1, |c| c != '"');
+                self.chars.next();
+                Some(CfgToken::Literal(literal))
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let rest =
+                    self.take_while(start + c.len_utf8(), |c| c.is_alphanumeric() || c == '_');
+                Some(CfgToken::Ident(
+                    &self.input[start..start + c.len_utf8() + rest.len()],
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(triple: &str) -> Platform {
+        Platform::try_from_triple(triple).unwrap()
+    }
+
+    #[test]
+    fn nested_cfg_expressions() {
+        let target = r#"cfg(all(unix, not(target_os = "macos")))"#;
+        assert_eq!(
+            CfgExpr::parse(target),
+            Some(CfgExpr::All(vec![
+                CfgExpr::Name("unix".to_owned()),
+                CfgExpr::Not(Box::new(CfgExpr::KeyValue(
+                    "target_os".to_owned(),
+                    "macos".to_owned()
+                ))),
+            ]))
+        );
+
+        assert!(platform("x86_64-unknown-linux-gnu").matches(target));
+        assert!(!platform("x86_64-apple-darwin").matches(target));
+        assert!(!platform("x86_64-pc-windows-msvc").matches(target));
+    }
+
+    #[test]
+    fn families_as_names() {
+        let wasm = platform("wasm32-unknown-unknown");
+        assert!(!wasm.matches("cfg(wasm)"));
+        assert!(wasm.matches(r#"cfg(target_family = "wasm")"#));
+        assert!(platform("x86_64-pc-windows-msvc").matches("cfg(windows)"));
+        assert!(!platform("x86_64-pc-windows-msvc").matches("cfg(unix)"));
+    }
+
+    #[test]
+    fn unparsable_expressions_keep_the_edge() {
+        assert_eq!(CfgExpr::parse("cfg(all(unix"), None);
+        assert!(platform("x86_64-pc-windows-msvc").matches("cfg(all(unix"));
+        assert!(!platform("x86_64-pc-windows-msvc").matches("x86_64-unknown-linux-gnu"));
+    }
+
+    #[test]
+    fn atomics_follow_the_target_not_the_pointer_width() {
+        let cases = [
+            ("x86_64-unknown-linux-gnu", ["8", "32", "64", "ptr"], true),
+            ("i686-unknown-linux-gnu", ["8", "32", "64", "ptr"], true),
+            ("thumbv6m-none-eabi", ["8", "32", "64", "ptr"], false),
+        ];
+        for (triple, widths, expected) in cases {
+            for width in widths {
+                let target = format!(r#"cfg(target_has_atomic = "{width}")"#);
+                assert_eq!(platform(triple).matches(&target), expected, "{triple} {width}");
+            }
+        }
+        assert!(!platform("i686-unknown-linux-gnu").matches(r#"cfg(target_has_atomic = "128")"#));
+    }
+}
@@ -0,0 +1,896 @@
+use crate::carriage::Carriage;
+use crate::cell::SichtCell;
+use crate::conditions::{Candidate, PredicateComposition};
+use crate::features::Activation;
+use crate::lookup::{Lookup, canonical};
+use crate::manifest::Pins;
+use crate::platform::Platform;
+use crate::store::{
+    Crate, DependencyKind, Kiste, Marker, Release, Skid, UnrolledCrate, published_versions,
+};
+use crate::waybill::Waybill;
+use semver::{Version, VersionReq};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The graph frozen once ingestion is done. A `Carriage` is what gets built and updated, a `Rack`
+/// is what gets queried: it holds plain owned data without any cells, so it is `Send + Sync` and
+/// lookups don't pay for borrow checks. Everything a single query needs to remember lives in its
+/// `Trip`, and any number of them can walk one rack at once.
+#[derive(Clone, Debug, Default)]
+pub struct Rack {
+    crates: BTreeMap<u32, RackedCrate>,
+    /// Crate ids by registry, crates.io being `None` and therefore first, then by canonical name.
+    registries: BTreeMap<Option<String>, HashMap<String, u32>>,
+    /// Release ids to the crate that published them.
+    owners: BTreeMap<u32, u32>,
+    /// Crate ids to the releases depending on them.
+    dependents: BTreeMap<u32, BTreeSet<u32>>,
+    pub waybill: Waybill,
+}
+
+const _: fn() = || {
+    fn shared<T: Send + Sync>() {}
+    shared::<Rack>();
+};
+
+#[derive(Clone, Debug)]
+pub struct RackedCrate {
+    pub krate: Kiste,
+    releases: BTreeMap<u32, RackedRelease>,
+    nums: HashMap<String, u32>,
+}
+
+/// A `Release` with its dependencies out of their cell and its version parsed once and for all.
+#[derive(Clone, Debug)]
+pub struct RackedRelease {
+    pub id: u32,
+    pub num: String,
+    pub version: Option<Version>,
+    pub yanked: bool,
+    pub created_at: String,
+    pub downloads: u32,
+    pub features: BTreeMap<String, Vec<String>>,
+    pub dependencies: Vec<Skid>,
+}
+
+/// What one query has seen on its way through the rack.
+#[derive(Debug, Default)]
+pub struct Trip {
+    /// Releases unrolled so far, with the features they were built with, and the depth they
+    /// were unrolled at. Met again with the same features they are only referenced, unless
+    /// `DEPTH` cut them shorter than it would now.
+    traversed: BTreeMap<(u32, Activation), usize>,
+    /// The path from the root to the node being unrolled.
+    ancestors: BTreeSet<u32>,
+    /// Crates whose dependents were listed so far and the depth that was left for them then.
+    listed: BTreeMap<u32, usize>,
+    /// The crates from the root of a `REVERSE` tree down to the dependent being listed.
+    descendants: BTreeSet<u32>,
+}
+
+impl Rack {
+    /// The crate going by `name`, looked up on crates.io first and in the other registries after.
+    /// Names are compared the way the `Lookup` does, `Serde-JSON` finds `serde_json`.
+    pub fn crate_id(&self, name: &str) -> Option<u32> {
+        let name = canonical(name);
+        self.registries
+            .values()
+            .find_map(|names| names.get(&name))
+            .copied()
+    }
+
+    pub fn crate_in_registry(&self, registry: Option<&str>, name: &str) -> Option<&RackedCrate> {
+        let id = self
+            .registries
+            .get(&registry.map(str::to_owned))?
+            .get(&canonical(name))?;
+        self.crates.get(id)
+    }
+
+    /// Every known crate name, sorted so completions can be looked up by prefix.
+    pub fn crate_names(&self) -> Vec<String> {
+        let mut names = self
+            .crates
+            .values()
+            .map(|krate| krate.krate.name.clone())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn search(&self, krate: &str, route: &Route<'_>) -> Option<UnrolledCrate> {
+        let root = self.crates.get(&self.crate_id(krate)?)?;
+        let release =
+            root.select_release(|release, _| route.admits(&Self::candidate(&root.krate, release)))?;
+        let activation = Activation::resolve(
+            &release.features,
+            route.features.iter().cloned(),
+            !route.no_default_features,
+        );
+
+        let mut trip = Trip::default();
+        trip.ancestors.insert(release.id);
+        Some(self.generate_from_crate(root, release, None, &activation, route, 0, &mut trip))
+    }
+
+    /// The node `release` of `krate` as `WHERE` sees it. Its version is the one the release was
+    /// published as, so version bounds hold for dependencies as they do for the root.
+    fn candidate<'c>(krate: &'c Kiste, release: &'c RackedRelease) -> Candidate<'c> {
+        Candidate {
+            version: release.version.as_ref(),
+            downloads: Some(u64::from(release.downloads)),
+            ..Candidate::new(&krate.name).with_created(krate.created_on())
+        }
+    }
+
+    fn edge_candidate<'c>(
+        krate: &'c Kiste,
+        release: &'c RackedRelease,
+        skid: &Skid,
+    ) -> Candidate<'c> {
+        Candidate {
+            kind: Some(skid.kind),
+            optional: Some(skid.optional),
+            ..Self::candidate(krate, release)
+        }
+    }
+
+    /// Dev-dependencies only matter to the crate being built, so they are followed from the root
+    /// and nowhere else. Nothing below `DEPTH` is unrolled.
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_from_crate(
+        &self,
+        krate: &RackedCrate,
+        release: &RackedRelease,
+        edge: Option<&Skid>,
+        activation: &Activation,
+        route: &Route<'_>,
+        depth: usize,
+        trip: &mut Trip,
+    ) -> UnrolledCrate {
+        let dependents = if route.depth.is_some_and(|max| depth >= max) {
+            Vec::default()
+        } else {
+            release
+                .dependencies
+                .iter()
+                .filter(|skid| depth == 0 || skid.kind != DependencyKind::Dev)
+                .filter_map(|skid| {
+                    self.generate_if_not_traversed(skid, activation, route, depth + 1, trip)
+                })
+                .collect()
+        };
+
+        UnrolledCrate {
+            crate_id: krate.krate.id,
+            name: krate.krate.name.clone(),
+            registry: krate.krate.registry.clone(),
+            version: Some(release.num.clone()),
+            req: edge.map(|skid| skid.req.clone()),
+            kind: edge.map(|skid| skid.kind),
+            features: activation.enabled(),
+            marker: None,
+            dependents,
+        }
+    }
+
+    /// Follows `skid` unless the features of its parent leave the dependency switched off. A
+    /// release already on the path is a cycle and one unrolled elsewhere in the tree with the
+    /// same features is only referenced, both end up as marked leaves. Under a `DEPTH` a release
+    /// first met deeper down is unrolled again, its earlier subtree was cut off sooner.
+    pub fn generate_if_not_traversed(
+        &self,
+        skid: &Skid,
+        parent: &Activation,
+        route: &Route<'_>,
+        depth: usize,
+        trip: &mut Trip,
+    ) -> Option<UnrolledCrate> {
+        let dependency = self.crates.get(&skid.dependency)?;
+        let name = skid.name.as_deref().unwrap_or(&dependency.krate.name);
+        if !parent.enables(name, skid) || !route.reaches(skid) {
+            return None;
+        }
+
+        let release = route
+            .pinned(&dependency.krate.name, &skid.req)
+            .or(skid.version.as_ref())
+            .and_then(|version| dependency.release(version));
+        let leaf = |marker| UnrolledCrate {
+            crate_id: skid.dependency,
+            name: dependency.krate.name.clone(),
+            registry: dependency.krate.registry.clone(),
+            version: release.map(|r| r.num.clone()),
+            req: Some(skid.req.clone()),
+            kind: Some(skid.kind),
+            features: Vec::default(),
+            marker,
+            dependents: Vec::default(),
+        };
+
+        match release {
+            Some(r) if !route.admits(&Self::edge_candidate(&dependency.krate, r, skid)) => None,
+            Some(r) if trip.ancestors.contains(&r.id) => Some(leaf(Some(Marker::Cycle))),
+            Some(r) => {
+                // The same release built with other features can pull in other dependencies.
+                let activation = parent.for_dependency(name, skid, &r.features);
+                let seen = (r.id, activation.clone());
+                if trip
+                    .traversed
+                    .get(&seen)
+                    .is_some_and(|at| route.depth.is_none() || *at <= depth)
+                {
+                    return Some(leaf(Some(Marker::Repeated)));
+                }
+                trip.traversed.insert(seen, depth);
+                trip.ancestors.insert(r.id);
+                let unrolled = self.generate_from_crate(
+                    dependency,
+                    r,
+                    Some(skid),
+                    &activation,
+                    route,
+                    depth,
+                    trip,
+                );
+                trip.ancestors.remove(&r.id);
+                Some(unrolled)
+            }
+            // An edge that matched no published version is still seen by its kind and flags.
+            None if !route.admits(&Self::unresolved_candidate(&dependency.krate, skid)) => None,
+            None => Some(leaf(None)),
+        }
+    }
+
+    fn accepted<'v>(skid: &Skid, versions: &'v [Version]) -> Option<&'v Version> {
+        let req = VersionReq::parse(&skid.req).ok()?;
+        versions.iter().filter(|version| req.matches(version)).max()
+    }
+
+    fn unresolved_candidate<'c>(krate: &'c Kiste, skid: &Skid) -> Candidate<'c> {
+        Candidate {
+            kind: Some(skid.kind),
+            optional: Some(skid.optional),
+            ..Candidate::new(&krate.name).with_created(krate.created_on())
+        }
+    }
+
+    /// Unrolls the crates depending on `krate` instead of its dependencies. Without a `DEPTH` only
+    /// the direct dependents are listed, the full reverse tree of a popular crate is most of the
+    /// registry.
+    pub fn search_reverse(&self, krate: &str, route: &Route<'_>) -> Option<UnrolledCrate> {
+        let root = self.crates.get(&self.crate_id(krate)?)?;
+        let admit = |release: &RackedRelease| route.admits(&Self::candidate(&root.krate, release));
+        let release = root.select_release(|release, _| admit(release))?;
+        let admitted = route.conditions.map(|_| {
+            root.releases
+                .values()
+                .filter(|&release| admit(release))
+                .filter_map(|release| release.version.clone())
+                .collect::<Vec<_>>()
+        });
+
+        let mut trip = Trip::default();
+        trip.descendants.insert(root.krate.id);
+        Some(UnrolledCrate {
+            crate_id: root.krate.id,
+            name: root.krate.name.clone(),
+            registry: root.krate.registry.clone(),
+            version: Some(release.num.clone()),
+            req: None,
+            kind: None,
+            features: Vec::default(),
+            marker: None,
+            dependents: self.generate_dependents(
+                root.krate.id,
+                admitted.as_deref(),
+                route,
+                route.depth.unwrap_or(1),
+                &mut trip,
+            ),
+        })
+    }
+
+    /// Lists the newest release of every crate with an edge onto `crate_id`. When `admitted` is
+    /// given only edges whose requirement accepts one of those versions count, without it
+    /// `WHERE version` doesn't apply. A dependent is
+    /// seen by `WHERE` with the version of `crate_id` its edge resolves to, the way a dependency
+    /// is seen with its own. Crates that merely dev-depend on `crate_id` are listed but not
+    /// followed any further, and like in a forward tree a crate met again is only referenced and
+    /// one on the path marks a cycle.
+    pub fn generate_dependents(
+        &self,
+        crate_id: u32,
+        admitted: Option<&[Version]>,
+        route: &Route<'_>,
+        depth: usize,
+        trip: &mut Trip,
+    ) -> Vec<UnrolledCrate> {
+        if depth == 0 {
+            return Vec::default();
+        }
+
+        let newest = self
+            .dependents
+            .get(&crate_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|version_id| {
+                let owner_id = *self.owners.get(version_id)?;
+                let release = self.crates.get(&owner_id)?.releases.get(version_id)?;
+                let skid = release.dependencies.iter().find(|skid| {
+                    skid.dependency == crate_id && skid.accepts_any(admitted) && route.reaches(skid)
+                })?;
+                Some((owner_id, release, skid))
+            })
+            .fold(
+                BTreeMap::<u32, (&RackedRelease, &Skid)>::new(),
+                |mut newest, (owner_id, release, skid)| {
+                    match newest.get(&owner_id) {
+                        Some((current, _)) if current.version >= release.version => {}
+                        _ => {
+                            newest.insert(owner_id, (release, skid));
+                        }
+                    }
+                    newest
+                },
+            );
+
+        let target = self.crates.get(&crate_id);
+        newest
+            .into_iter()
+            .filter_map(|(owner_id, (release, skid))| {
+                let owner = self.crates.get(&owner_id)?;
+                // An edge that resolved to nothing, e.g. onto yanked releases only, lands on the
+                // newest admitted version its requirement accepts.
+                let landed = skid
+                    .version
+                    .as_deref()
+                    .and_then(|num| target?.release(num)?.version.as_ref())
+                    .or_else(|| Self::accepted(skid, admitted?));
+                // Only the direct dependents are bound by the versions of the root, the ones
+                // further down depend on other crates.
+                let candidate = Candidate {
+                    version: landed,
+                    unversioned: admitted.is_none(),
+                    ..Self::edge_candidate(&owner.krate, release, skid)
+                };
+                if !route.admits(&candidate) {
+                    return None;
+                }
+
+                let left = depth - 1;
+                let followed = skid.kind != DependencyKind::Dev;
+                let marker = if trip.descendants.contains(&owner_id) {
+                    Some(Marker::Cycle)
+                } else if followed && trip.listed.get(&owner_id).is_some_and(|seen| *seen >= left) {
+                    Some(Marker::Repeated)
+                } else {
+                    None
+                };
+                let dependents = if marker.is_some() || !followed {
+                    Vec::default()
+                } else {
+                    trip.listed.insert(owner_id, left);
+                    trip.descendants.insert(owner_id);
+                    let dependents = self.generate_dependents(owner_id, None, route, left, trip);
+                    trip.descendants.remove(&owner_id);
+                    dependents
+                };
+
+                Some(UnrolledCrate {
+                    crate_id: owner_id,
+                    name: owner.krate.name.clone(),
+                    registry: owner.krate.registry.clone(),
+                    version: Some(release.num.clone()),
+                    req: Some(skid.req.clone()),
+                    kind: Some(skid.kind),
+                    features: Vec::default(),
+                    marker,
+                    dependents,
+                })
+            })
+            .collect()
+    }
+}
+
+impl RackedCrate {
+    /// Picks the highest release among those admitted, preferring releases that aren't yanked.
+    pub fn select_release<F>(&self, admit: F) -> Option<&RackedRelease>
+    where
+        F: Fn(&RackedRelease, &Version) -> bool,
+    {
+        self.releases
+            .values()
+            .filter_map(|release| release.version.as_ref().map(|version| (release, version)))
+            .filter(|(release, version)| admit(release, version))
+            .max_by(|(left, left_version), (right, right_version)| {
+                (!left.yanked, left_version).cmp(&(!right.yanked, right_version))
+            })
+            .map(|(release, _)| release)
+    }
+
+    pub fn release(&self, num: &str) -> Option<&RackedRelease> {
+        self.releases.get(self.nums.get(num)?)
+    }
+
+    /// Versions a requirement may resolve to, highest first. Yanked releases are left out.
+    pub fn published_versions(&self) -> Vec<(Version, String)> {
+        published_versions(
+            self.releases
+                .values()
+                .map(|release| (release.yanked, release.version.clone(), &release.num)),
+        )
+    }
+}
+
+/// Freezing moves the graph over, the carriage is usually as big as the rack it turns into.
+impl From<Carriage> for Rack {
+    fn from(carriage: Carriage) -> Self {
+        let lookup = carriage.lookup.into_inner();
+        let mut map = carriage.map.into_inner();
+        Rack {
+            crates: map
+                .iter_mut()
+                .map(|(id, krate): (&u32, &mut Crate)| (*id, RackedCrate::take(krate)))
+                .collect(),
+            registries: lookup.names,
+            owners: lookup.dependency_version,
+            dependents: lookup.dependents,
+            waybill: carriage.waybill,
+        }
+    }
+}
+
+/// Thawing is only needed to update the graph, e.g. from a registry index, and to cache it.
+impl From<Rack> for Carriage {
+    fn from(rack: Rack) -> Self {
+        let mut lookup = Lookup::with_krate(rack.crates.values().map(|krate| &krate.krate));
+        lookup.dependency_version = rack.owners;
+        lookup.dependents = rack.dependents;
+
+        let map = Carriage::index_crates(rack.crates.into_values().map(Crate::from).collect());
+        let mut carriage = Carriage::new(SichtCell::new(map), lookup);
+        carriage.waybill = rack.waybill;
+        carriage
+    }
+}
+
+impl RackedCrate {
+    /// Takes the releases out of `krate`, leaving it empty.
+    fn take(krate: &mut Crate) -> Self {
+        let releases = std::mem::take(&mut krate.versions)
+            .into_inner()
+            .iter_mut()
+            .map(|(id, release): (&u32, &mut Release)| (*id, RackedRelease::take(release)))
+            .collect::<BTreeMap<_, _>>();
+        let nums = releases
+            .values()
+            .map(|release| (release.num.clone(), release.id))
+            .collect();
+        Self {
+            krate: std::mem::take(&mut krate.krate),
+            releases,
+            nums,
+        }
+    }
+}
+
+impl From<RackedCrate> for Crate {
+    fn from(krate: RackedCrate) -> Self {
+        Crate::with_releases(krate.krate, krate.releases.into_values().map(Release::from))
+    }
+}
+
+impl RackedRelease {
+    /// Takes the fields out of `release`, leaving it empty.
+    fn take(release: &mut Release) -> Self {
+        Self {
+            id: release.id,
+            version: release.version(),
+            num: std::mem::take(&mut release.num),
+            yanked: release.yanked,
+            created_at: std::mem::take(&mut release.created_at),
+            downloads: release.downloads,
+            features: std::mem::take(&mut release.features),
+            dependencies: std::mem::take(&mut release.dependencies).into_inner(),
+        }
+    }
+}
+
+impl From<RackedRelease> for Release {
+    fn from(release: RackedRelease) -> Self {
+        Self {
+            id: release.id,
+            num: release.num,
+            yanked: release.yanked,
+            created_at: release.created_at,
+            downloads: release.downloads,
+            features: release.features,
+            dependencies: SichtCell::new(release.dependencies),
+        }
+    }
+}
+
+/// The parts of a query that steer the traversal itself.
+#[derive(Clone, Debug, Default)]
+pub struct Route<'q> {
+    pub conditions: Option<&'q PredicateComposition>,
+    pub depth: Option<usize>,
+    pub features: &'q [String],
+    pub no_default_features: bool,
+    pub target: Option<Platform>,
+    pub pins: Option<&'q Pins>,
+}
+
+impl Route<'_> {
+    pub fn admits(&self, candidate: &Candidate<'_>) -> bool {
+        self.conditions.is_none_or(|c| c.admits(candidate))
+    }
+
+    /// Edges declared under a `[target]` table only count when they apply to the chosen target.
+    pub fn reaches(&self, skid: &Skid) -> bool {
+        match (&self.target, &skid.target) {
+            (Some(platform), Some(target)) => platform.matches(target),
+            _ => true,
+        }
+    }
+
+    /// The highest version locked in `Cargo.lock` that satisfies `req`, if a lockfile was given.
+    pub fn pinned(&self, name: &str, req: &str) -> Option<&String> {
+        let req = VersionReq::parse(req).ok()?;
+        self.pins?
+            .get(name)?
+            .iter()
+            .find(|(version, _)| req.matches(version))
+            .map(|(_, num)| num)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::joystick::Query;
+
+    /// A release of a test rack: the crate id, the release id, the crate name, the version and
+    /// the dependencies as crate id, requirement and kind.
+    type Spec<'s> = (u32, u32, &'s str, &'s str, &'s [(u32, &'s str, DependencyKind)]);
+
+    /// A rack of `releases`, their edges resolved and indexed for reverse traversal.
+    fn rack_of(releases: &[Spec<'_>]) -> Rack {
+        let mut crates = BTreeMap::<u32, Crate>::new();
+        for &(crate_id, id, name, num, dependencies) in releases {
+            let dependencies = dependencies
+                .iter()
+                .map(|&(dependency, req, kind)| {
+                    Skid::new_with_dependency(dependency, req.to_owned(), kind)
+                })
+                .collect();
+            crates
+                .entry(crate_id)
+                .or_insert_with(|| Crate::new(Kiste::indexed(crate_id, name.to_owned())))
+                .add_release(Release {
+                    id,
+                    num: num.to_owned(),
+                    yanked: false,
+                    created_at: String::new(),
+                    downloads: 0,
+                    features: BTreeMap::default(),
+                    dependencies: SichtCell::new(dependencies),
+                });
+        }
+
+        let carriage = Carriage::from_map(Carriage::index_crates(crates.into_values().collect()));
+        let mut lookup = carriage.lookup.borrow_mut();
+        for &(crate_id, id, _, _, dependencies) in releases {
+            lookup.insert_dependency_relation(id, crate_id);
+            for &(dependency, _, _) in dependencies {
+                lookup.insert_dependent(dependency, id);
+            }
+        }
+        drop(lookup);
+        carriage.resolve_dependencies();
+        Rack::from(carriage)
+    }
+
+    /// A rack of crates at `1.0.0`, each depending on the crates listed next to it in order.
+    fn rack(crates: &[(u32, &str, &[u32])]) -> Rack {
+        let edges = crates
+            .iter()
+            .map(|(_, _, dependencies)| {
+                dependencies
+                    .iter()
+                    .map(|dependency| (*dependency, "^1", DependencyKind::Normal))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let releases = crates
+            .iter()
+            .zip(&edges)
+            .map(|((id, name, _), edges)| (*id, id * 10, *name, "1.0.0", edges.as_slice()))
+            .collect::<Vec<_>>();
+        rack_of(&releases)
+    }
+
+    fn run(rack: &Rack, query: &str) -> Option<UnrolledCrate> {
+        Query::parse(query).unwrap().apply_to_rack(rack)
+    }
+
+    fn names(unrolled: &UnrolledCrate) -> Vec<(&str, Option<Marker>, usize)> {
+        unrolled
+            .dependents
+            .iter()
+            .map(|dependent| {
+                let children = dependent.dependents.len();
+                (dependent.name.as_str(), dependent.marker, children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shared_release_first_met_at_the_depth_limit_is_unrolled_again() {
+        // top -> deep -> shared -> leaf, and top -> shared directly after.
+        let rack = rack(&[
+            (1, "top", &[2, 3]),
+            (2, "deep", &[3]),
+            (3, "shared", &[4]),
+            (4, "leaf", &[]),
+        ]);
+        let route = Route {
+            depth: Some(2),
+            ..Route::default()
+        };
+
+        let unrolled = rack.search("top", &route).unwrap();
+        assert_eq!(names(&unrolled), [("deep", None, 1), ("shared", None, 1)]);
+        assert_eq!(names(&unrolled.dependents[0]), [("shared", None, 0)]);
+        assert_eq!(names(&unrolled.dependents[1]), [("leaf", None, 0)]);
+    }
+
+    #[test]
+    fn shared_release_is_referenced_without_a_depth() {
+        let rack = rack(&[
+            (1, "top", &[2, 3]),
+            (2, "deep", &[3]),
+            (3, "shared", &[4]),
+            (4, "leaf", &[]),
+        ]);
+
+        let unrolled = rack.search("top", &Route::default()).unwrap();
+        assert_eq!(
+            names(&unrolled),
+            [("deep", None, 1), ("shared", Some(Marker::Repeated), 0)]
+        );
+    }
+
+    #[test]
+    fn version_bounds_keep_the_edges_they_hold_for() {
+        let rack = rack(&[
+            (1, "serde", &[2, 3]),
+            (2, "serde_derive", &[]),
+            (3, "itoa", &[]),
+        ]);
+
+        let unrolled = run(
+            &rack,
+            "LIFT serde WHERE version >= 1.0 AND (kind = normal OR optional = false)",
+        )
+        .unwrap();
+        assert_eq!(
+            names(&unrolled),
+            [("serde_derive", None, 0), ("itoa", None, 0)]
+        );
+        assert!(run(&rack, "LIFT serde WHERE version >= 2.0").is_none());
+
+        let reverse = run(&rack, "LIFT itoa REVERSE WHERE version >= 1.0").unwrap();
+        assert_eq!(names(&reverse), [("serde", None, 0)]);
+    }
+
+    #[test]
+    fn reverse_lists_direct_dependents_without_a_depth() {
+        let rack = rack(&[
+            (1, "base", &[]),
+            (2, "left", &[1]),
+            (3, "right", &[1]),
+            (4, "top", &[2, 3]),
+        ]);
+
+        let reverse = run(&rack, "LIFT base REVERSE").unwrap();
+        assert_eq!(names(&reverse), [("left", None, 0), ("right", None, 0)]);
+    }
+
+    #[test]
+    fn reverse_depth_references_a_dependent_met_again() {
+        let rack = rack(&[
+            (1, "base", &[]),
+            (2, "left", &[1]),
+            (3, "right", &[1]),
+            (4, "top", &[2, 3]),
+        ]);
+
+        let reverse = run(&rack, "LIFT base REVERSE DEPTH 3").unwrap();
+        assert_eq!(names(&reverse), [("left", None, 1), ("right", None, 1)]);
+        assert_eq!(names(&reverse.dependents[0]), [("top", None, 0)]);
+        assert_eq!(
+            names(&reverse.dependents[1]),
+            [("top", Some(Marker::Repeated), 0)]
+        );
+    }
+
+    #[test]
+    fn reverse_depth_marks_cycles() {
+        let rack = rack(&[(1, "egg", &[2]), (2, "hen", &[1])]);
+
+        let reverse = run(&rack, "LIFT egg REVERSE DEPTH 3").unwrap();
+        assert_eq!(names(&reverse), [("hen", None, 1)]);
+        assert_eq!(
+            names(&reverse.dependents[0]),
+            [("egg", Some(Marker::Cycle), 0)]
+        );
+    }
+
+    #[test]
+    fn reverse_keeps_dependents_of_admitted_versions() {
+        let rack = rack_of(&[
+            (1, 10, "base", "1.0.0", &[]),
+            (1, 11, "base", "2.0.0", &[]),
+            (2, 20, "old", "1.0.0", &[(1, "^1", DependencyKind::Normal)]),
+            (3, 30, "new", "1.0.0", &[(1, "^2", DependencyKind::Normal)]),
+        ]);
+
+        let reverse = run(&rack, "LIFT base REVERSE WHERE version >= 2.0").unwrap();
+        assert_eq!(reverse.version.as_deref(), Some("2.0.0"));
+        assert_eq!(names(&reverse), [("new", None, 0)]);
+
+        let reverse = run(&rack, "LIFT base REVERSE WHERE version < 2.0").unwrap();
+        assert_eq!(names(&reverse), [("old", None, 0)]);
+    }
+
+    #[test]
+    fn reverse_keeps_dependents_whose_edge_resolved_to_nothing() {
+        let mut rack = rack_of(&[
+            (1, 10, "base", "1.0.0", &[]),
+            (1, 11, "base", "2.0.0", &[]),
+            (2, 20, "user", "1.0.0", &[(1, "^2", DependencyKind::Normal)]),
+        ]);
+        // As if every release the requirement accepts had been yanked since.
+        let user = rack.crates.get_mut(&2).unwrap();
+        user.releases.get_mut(&20).unwrap().dependencies[0].version = None;
+
+        let reverse = run(&rack, "LIFT base REVERSE WHERE version >= 2.0").unwrap();
+        assert_eq!(names(&reverse), [("user", None, 0)]);
+        let reverse = run(&rack, "LIFT base REVERSE WHERE version < 2.0").unwrap();
+        assert!(reverse.dependents.is_empty());
+    }
+
+    #[test]
+    fn reverse_version_bounds_only_hold_for_direct_dependents() {
+        let rack = rack_of(&[
+            (1, 10, "base", "1.0.0", &[]),
+            (1, 11, "base", "2.0.0", &[]),
+            (2, 20, "mid", "1.0.0", &[(1, "^2", DependencyKind::Normal)]),
+            (3, 30, "top", "1.0.0", &[(2, "^1", DependencyKind::Normal)]),
+        ]);
+
+        let reverse = run(&rack, "LIFT base REVERSE DEPTH 2 WHERE version >= 2.0").unwrap();
+        assert_eq!(names(&reverse), [("mid", None, 1)]);
+        assert_eq!(names(&reverse.dependents[0]), [("top", None, 0)]);
+    }
+
+    #[test]
+    fn dev_dependencies_are_only_followed_from_the_root() {
+        let rack = rack_of(&[
+            (
+                1,
+                10,
+                "app",
+                "1.0.0",
+                &[(2, "^1", DependencyKind::Normal), (4, "^1", DependencyKind::Dev)],
+            ),
+            (2, 20, "lib", "1.0.0", &[(3, "^1", DependencyKind::Dev)]),
+            (3, 30, "helper", "1.0.0", &[]),
+            (4, 40, "tester", "1.0.0", &[]),
+        ]);
+
+        let app = run(&rack, "LIFT app").unwrap();
+        assert_eq!(names(&app), [("lib", None, 0), ("tester", None, 0)]);
+        assert_eq!(app.dependents[1].kind, Some(DependencyKind::Dev));
+
+        let lib = run(&rack, "LIFT lib").unwrap();
+        assert_eq!(names(&lib), [("helper", None, 0)]);
+    }
+
+    #[test]
+    fn kind_conditions_filter_edges() {
+        let rack = rack_of(&[
+            (
+                1,
+                10,
+                "app",
+                "1.0.0",
+                &[
+                    (2, "^1", DependencyKind::Normal),
+                    (3, "^1", DependencyKind::Build),
+                    (4, "^1", DependencyKind::Dev),
+                ],
+            ),
+            (2, 20, "lib", "1.0.0", &[]),
+            (3, 30, "cc", "1.0.0", &[]),
+            (4, 40, "tester", "1.0.0", &[]),
+        ]);
+
+        let cases: [(&str, &[&str]); 4] = [
+            ("LIFT app WHERE kind = dev", &["tester"]),
+            ("LIFT app WHERE kind != dev", &["lib", "cc"]),
+            ("LIFT app WHERE kind IN (normal, build)", &["lib", "cc"]),
+            ("LIFT app WHERE kind = build OR kind = dev", &["cc", "tester"]),
+        ];
+        for (query, expected) in cases {
+            let unrolled = run(&rack, query).unwrap();
+            let kept = unrolled
+                .dependents
+                .iter()
+                .map(|dependent| dependent.name.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(kept, expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn kind_conditions_filter_unresolved_edges() {
+        let rack = rack_of(&[
+            (
+                1,
+                10,
+                "app",
+                "1.0.0",
+                &[(2, "^9", DependencyKind::Normal), (3, "^9", DependencyKind::Dev)],
+            ),
+            (2, 20, "lib", "1.0.0", &[]),
+            (3, 30, "tester", "1.0.0", &[]),
+        ]);
+
+        let all = run(&rack, "LIFT app").unwrap();
+        assert_eq!(names(&all), [("lib", None, 0), ("tester", None, 0)]);
+        assert_eq!(all.dependents[0].version, None);
+
+        let dev = run(&rack, "LIFT app WHERE kind = dev").unwrap();
+        assert_eq!(names(&dev), [("tester", None, 0)]);
+        let optional = run(&rack, "LIFT app WHERE optional = true").unwrap();
+        assert!(optional.dependents.is_empty());
+    }
+
+    #[test]
+    fn shared_release_is_unrolled_again_with_more_features() {
+        fn release(rack: &mut Rack, crate_id: u32, id: u32) -> &mut RackedRelease {
+            rack.crates
+                .get_mut(&crate_id)
+                .and_then(|krate| krate.releases.get_mut(&id))
+                .unwrap()
+        }
+
+        // app -> a -> shared, app -> b -> shared with `extra`, which switches `bonus` on.
+        let mut rack = rack(&[
+            (1, "app", &[2, 3]),
+            (2, "a", &[4]),
+            (3, "b", &[4]),
+            (4, "shared", &[5]),
+            (5, "bonus", &[]),
+        ]);
+        let shared = release(&mut rack, 4, 40);
+        shared.features = BTreeMap::from([("extra".to_owned(), vec!["dep:bonus".to_owned()])]);
+        shared.dependencies[0].optional = true;
+        release(&mut rack, 3, 30).dependencies[0].features = vec!["extra".to_owned()];
+
+        let unrolled = run(&rack, "LIFT app").unwrap();
+        assert_eq!(names(&unrolled), [("a", None, 1), ("b", None, 1)]);
+        assert_eq!(names(&unrolled.dependents[0]), [("shared", None, 0)]);
+        assert_eq!(names(&unrolled.dependents[1]), [("shared", None, 1)]);
+        assert_eq!(
+            names(&unrolled.dependents[1].dependents[0]),
+            [("bonus", None, 0)]
+        );
+        assert_eq!(unrolled.dependents[1].dependents[0].features, ["extra"]);
+    }
+}
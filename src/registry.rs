@@ -0,0 +1,124 @@
+use crate::waybill::Waybill;
+use anyhow::Result;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A registry besides crates.io whose crates are merged into the graph. `source` is either a
+/// dump in the crates.io format or a checkout of the registry's index.
+#[derive(Clone, Debug, Deserialize)]
+#[non_exhaustive]
+pub struct Registry {
+    pub name: String,
+    /// The index URL dependencies use to point into this registry.
+    #[serde(default)]
+    pub index: Option<String>,
+    pub source: PathBuf,
+}
+
+impl Registry {
+    /// The registry `name`, read from `source`. Without an index URL no dependency can point
+    /// into it, see [`Registry::with_index`].
+    #[must_use]
+    pub fn new<P: AsRef<Path>>(name: &str, source: P) -> Self {
+        Self {
+            name: name.to_owned(),
+            index: None,
+            source: source.as_ref().to_owned(),
+        }
+    }
+
+    /// The index URL dependencies use to point into this registry.
+    #[must_use]
+    pub fn with_index(mut self, index: String) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    #[must_use]
+    pub fn is_dump(&self) -> bool {
+        self.source.is_file()
+    }
+
+    /// Identifies the source, a dump like the crates.io one, a checkout by its files.
+    pub(crate) fn waybill(&self) -> Result<Waybill> {
+        if self.is_dump() {
+            Waybill::for_dump(&self.source, None)
+        } else {
+            Waybill::for_checkout(&self.source)
+        }
+    }
+
+    /// Whether the source is still the one `waybill` was written for.
+    pub(crate) fn is_unchanged(&self, waybill: &Waybill) -> bool {
+        if self.is_dump() {
+            waybill.matches(&self.source).unwrap_or(false)
+        } else {
+            self.source.is_dir()
+                && Waybill::for_checkout(&self.source).is_ok_and(|current| current == *waybill)
+        }
+    }
+
+    fn serves(&self, url: &str) -> bool {
+        self.index
+            .as_deref()
+            .is_some_and(|index| normalize(index) == normalize(url))
+    }
+}
+
+/// Which registry a dependency's `registry` URL points at: `Some(None)` for crates.io,
+/// `Some(Some(name))` for one of `registries` and `None` for one that isn't configured.
+#[allow(clippy::option_option)]
+pub fn resolve_url<'r>(registries: &'r [Registry], url: &str) -> Option<Option<&'r str>> {
+    if is_crates_io(url) {
+        return Some(None);
+    }
+    registries
+        .iter()
+        .find(|registry| registry.serves(url))
+        .map(|registry| Some(registry.name.as_str()))
+}
+
+fn is_crates_io(url: &str) -> bool {
+    matches!(
+        normalize(url),
+        "https://github.com/rust-lang/crates.io-index" | "https://index.crates.io"
+    )
+}
+
+fn normalize(url: &str) -> &str {
+    let url = url.strip_prefix("sparse+").unwrap_or(url);
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(name: &str, index: &str) -> Registry {
+        Registry::new(name, PathBuf::new()).with_index(index.to_owned())
+    }
+
+    #[test]
+    fn urls_resolve_to_crates_io_or_a_configured_registry() {
+        let registries = [
+            registry("corp", "https://git.corp.example/index.git"),
+            registry("lab", "sparse+https://lab.example/index/"),
+        ];
+        let cases = [
+            ("https://github.com/rust-lang/crates.io-index", Some(None)),
+            (
+                "https://github.com/rust-lang/crates.io-index.git",
+                Some(None),
+            ),
+            ("sparse+https://index.crates.io/", Some(None)),
+            ("https://git.corp.example/index", Some(Some("corp"))),
+            ("https://git.corp.example/index.git/", Some(Some("corp"))),
+            ("https://lab.example/index", Some(Some("lab"))),
+            ("https://elsewhere.example/index", None),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(resolve_url(&registries, url), expected, "{url}");
+        }
+    }
+}
@@ -1,14 +1,20 @@
 use crate::carriage::Carriage;
-use crate::store::{Crate, Kiste, Skid};
-use serde::{Deserialize, Deserializer, de::Error, de::SeqAccess, de::Visitor};
-use serde::{Serialize, Serializer, ser::SerializeMap, ser::SerializeStruct};
+use crate::cell::SichtCell;
+use crate::lookup::Lookup;
+use crate::store::{Crate, Kiste, Release};
+use crate::waybill::Waybill;
+use serde::de::{Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde::{Serialize, Serializer, ser::SerializeStruct};
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::fmt::Formatter;
 use std::rc::Rc;
 
 pub struct CarriageSer {
+    pub waybill: Waybill,
     pub map: Rc<RefCell<BTreeMap<u32, CrateSer>>>,
+    pub lookup: Lookup,
 }
 
 impl CarriageSer {
@@ -21,13 +27,11 @@ impl CarriageSer {
             .collect();
 
         Self {
+            waybill: x.waybill.clone(),
             map: Rc::new(RefCell::new(map)),
+            lookup: x.lookup.borrow().clone(),
         }
     }
-
-    pub fn insert(&self, key: u32, value: CrateSer) {
-        self.map.borrow_mut().insert(key, value);
-    }
 }
 
 impl Serialize for CarriageSer {
@@ -35,12 +39,11 @@ impl Serialize for CarriageSer {
     where
         S: Serializer,
     {
-        let len = self.map.borrow().len();
-        let mut map = serializer.serialize_map(Some(len))?;
-        self.map.borrow().iter().for_each(|(k, v)| {
-            let _ = map.serialize_entry(&k, &v);
-        });
-        map.end()
+        let mut state = serializer.serialize_struct("CarriageSer", 3)?;
+        state.serialize_field("waybill", &self.waybill)?;
+        state.serialize_field("map", &*self.map.borrow())?;
+        state.serialize_field("lookup", &self.lookup)?;
+        state.end()
     }
 }
 
@@ -49,30 +52,117 @@ impl<'de> Deserialize<'de> for CarriageSer {
     where
         D: Deserializer<'de>,
     {
-        let map = BTreeMap::deserialize(deserializer)?;
+        deserializer.deserialize_struct(
+            "CarriageSer",
+            &["waybill", "map", "lookup"],
+            CarriageSerVisitor,
+        )
+    }
+}
+
+struct CarriageSerVisitor;
+
+impl<'de> Visitor<'de> for CarriageSerVisitor {
+    type Value = CarriageSer;
+
+    fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "expecting a carriage to be delivered")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let waybill = seq
+            .next_element()?
+            .ok_or_else(|| Error::invalid_length(0, &self))?;
+        let map = seq
+            .next_element()?
+            .ok_or_else(|| Error::invalid_length(1, &self))?;
+        let lookup = seq
+            .next_element()?
+            .ok_or_else(|| Error::invalid_length(2, &self))?;
+
         Ok(CarriageSer {
+            waybill,
             map: Rc::new(RefCell::new(map)),
+            lookup,
         })
     }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let (mut waybill, mut map, mut lookup) = (None, None, None);
+        while let Some(FieldName(key)) = access.next_key()? {
+            match key.as_str() {
+                "waybill" => waybill = Some(access.next_value()?),
+                "map" => map = Some(access.next_value()?),
+                "lookup" => lookup = Some(access.next_value()?),
+                _ => {
+                    access.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(CarriageSer {
+            waybill: waybill.unwrap_or_default(),
+            map: Rc::new(RefCell::new(
+                map.ok_or_else(|| Error::missing_field("map"))?,
+            )),
+            lookup: lookup.ok_or_else(|| Error::missing_field("lookup"))?,
+        })
+    }
+}
+
+/// A field name of a struct written as a map. RON hands those out as identifiers, which don't
+/// deserialize as a `String`.
+struct FieldName(String);
+
+impl<'de> Deserialize<'de> for FieldName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(FieldNameVisitor)
+    }
+}
+
+struct FieldNameVisitor;
+
+impl Visitor<'_> for FieldNameVisitor {
+    type Value = FieldName;
+
+    fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "expecting a field name")
+    }
+
+    fn visit_str<E>(self, field: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(FieldName(field.to_owned()))
+    }
 }
 
 pub struct CrateSer {
     krate: Kiste,
-    dependencies: Rc<RefCell<BTreeMap<u32, Skid>>>,
+    versions: Rc<RefCell<BTreeMap<u32, Release>>>,
 }
 
 impl From<Crate> for CrateSer {
     fn from(x: Crate) -> Self {
         let map = x
-            .dependencies
+            .versions
             .borrow()
             .iter()
-            .map(|(k, d)| (*k, d.to_owned()))
+            .map(|(k, r)| (*k, r.to_owned()))
             .collect();
 
         CrateSer {
             krate: x.krate,
-            dependencies: Rc::new(RefCell::new(map)),
+            versions: Rc::new(RefCell::new(map)),
         }
     }
 }
@@ -84,7 +174,7 @@ impl Serialize for CrateSer {
     {
         let mut state = serializer.serialize_struct("CrateSer", 2)?;
         state.serialize_field("krate", &self.krate)?;
-        state.serialize_field("dependencies", &*self.dependencies.borrow())?;
+        state.serialize_field("versions", &*self.versions.borrow())?;
         state.end()
     }
 }
@@ -94,13 +184,36 @@ impl<'de> Deserialize<'de> for CrateSer {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_struct("CrateSer", &["krate", "dependencies"], CrateSerVisitor)
+        deserializer.deserialize_struct("CrateSer", &["krate", "versions"], CrateSerVisitor)
     }
 }
 
 impl From<CarriageSer> for Carriage {
     fn from(x: CarriageSer) -> Self {
-        todo!()
+        let map = Carriage::index_crates(
+            Rc::into_inner(x.map)
+                .map(RefCell::into_inner)
+                .unwrap_or_default()
+                .into_values()
+                .map(Crate::from)
+                .collect(),
+        );
+        // The name index isn't part of the cache, every crate in the map carries its name.
+        let mut lookup = x.lookup;
+        lookup.seed_krates(map.iter().map(|(_, krate): (&u32, &Crate)| &krate.krate));
+
+        let mut carriage = Carriage::new(SichtCell::new(map), lookup);
+        carriage.waybill = x.waybill;
+        carriage
+    }
+}
+
+impl From<CrateSer> for Crate {
+    fn from(x: CrateSer) -> Self {
+        let versions = Rc::into_inner(x.versions)
+            .map(RefCell::into_inner)
+            .unwrap_or_default();
+        Crate::with_releases(x.krate, versions.into_values())
     }
 }
 
@@ -120,13 +233,95 @@ impl<'de> Visitor<'de> for CrateSerVisitor {
         let krate = seq
             .next_element()?
             .ok_or_else(|| Error::invalid_length(0, &self))?;
-        let dependencies = seq
+        let versions = seq
             .next_element()?
-            .ok_or_else(|| Error::invalid_length(0, &self))?;
+            .ok_or_else(|| Error::invalid_length(1, &self))?;
 
         Ok(CrateSer {
             krate,
-            dependencies: Rc::new(RefCell::new(dependencies)),
+            versions: Rc::new(RefCell::new(versions)),
         })
     }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let (mut krate, mut versions) = (None, None);
+        while let Some(FieldName(key)) = access.next_key()? {
+            match key.as_str() {
+                "krate" => krate = Some(access.next_value()?),
+                "versions" => versions = Some(access.next_value()?),
+                _ => {
+                    access.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(CrateSer {
+            krate: krate.ok_or_else(|| Error::missing_field("krate"))?,
+            versions: Rc::new(RefCell::new(versions.unwrap_or_default())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crusher::Lager;
+    use crate::fixture::{self, scratch};
+    use crate::fs::Mast;
+    use crate::rack::Rack;
+    use anyhow::Result;
+    use std::fs;
+
+    #[test]
+    fn lager_round_trips_the_carriage() -> Result<()> {
+        let dir = scratch("lager");
+        let dump = dir.join("db-dump.tar.gz");
+        fixture::write_dump(&dump)?;
+
+        let carriage = Carriage::unarchive(&dump, true)?;
+
+        let mast = Mast::path(&dump);
+        let crushed = CarriageSer::from_carriage(&carriage);
+        let restored = [Lager::Ron, Lager::Bale].map(|lager| -> Result<Carriage> {
+            let path = dir.join(lager.file_name());
+            lager.crush(&mast, &path, &crushed)?;
+            Ok(lager.uncrush(fs::read(&path)?)?.into())
+        });
+
+        for restored in restored {
+            let restored = restored?;
+            assert_eq!(
+                ron::to_string(&crushed)?,
+                ron::to_string(&CarriageSer::from_carriage(&restored))?,
+            );
+
+            let map = restored.map.borrow();
+            let serde_derive = map
+                .get_with_outer_key(&"serde_derive".to_owned())
+                .expect("crates are keyed by name");
+            assert!(
+                serde_derive
+                    .versions
+                    .borrow()
+                    .get_with_outer_key(&"1.0.100".to_owned())
+                    .is_some_and(|release| release.yanked)
+            );
+            assert_eq!(
+                restored.lookup.borrow().krate.values().collect::<Vec<_>>(),
+                ["serde", "serde_derive"]
+            );
+            assert_eq!(
+                restored
+                    .lookup
+                    .borrow()
+                    .crate_in_registry(None, "Serde-Derive"),
+                Some(&2)
+            );
+        }
+        assert_eq!(Rack::from(carriage).crate_id("SERDE-derive"), Some(2));
+        Ok(())
+    }
 }
@@ -1,45 +1,69 @@
 use crate::carriage::Carriage;
 use crate::cell::SichtCell;
-use anyhow::Result;
-use serde::de::Visitor;
-use serde::{Deserialize, Deserializer, Serialize};
+use crate::tally::Tally;
+use chrono::NaiveDate;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
 use sicht::SichtMap;
 use std::collections::BTreeMap;
-use std::fmt::{Debug, Formatter};
-use std::marker::PhantomData;
 
 #[derive(Debug, Clone)]
 pub struct Crate {
     pub krate: Kiste,
-    pub dependencies: SichtCell<SichtMap<u32, String, Skid>>,
+    pub versions: SichtCell<SichtMap<u32, String, Release>>,
 }
 impl Crate {
     pub fn new(krate: Kiste) -> Self {
         Self {
             krate,
-            dependencies: SichtCell::default(),
+            versions: SichtCell::default(),
         }
     }
 
-    pub fn add_dependency(&self, dependency: &Self) {
-        // dbg!(&self, &dependency);
-        let key = dependency.krate.id;
-        self.dependencies.borrow_mut().insert_with_both_keys(
-            key,
-            dependency.krate.name.clone(),
-            Skid::new_with_dependency(key),
-        );
+    pub fn with_releases<I: IntoIterator<Item = Release>>(krate: Kiste, releases: I) -> Self {
+        let krate = Self::new(krate);
+        releases
+            .into_iter()
+            .for_each(|release| krate.add_release(release));
+        krate
+    }
+
+    pub fn add_release(&self, release: Release) {
+        self.versions
+            .borrow_mut()
+            .insert_with_both_keys(release.id, release.num.clone(), release);
+    }
+
+    pub fn release(&self, num: &String) -> Option<Release> {
+        self.versions.borrow().get_with_outer_key(num).cloned()
     }
 
-    pub fn add_dependency_with_fields(&self, dependency_id: u32, dependency_name: &str) {
-        self.dependencies.borrow_mut().insert_with_both_keys(
-            dependency_id,
-            dependency_name.to_owned(),
-            Skid::new_with_dependency(dependency_id),
-        );
+    /// Versions a requirement may resolve to, highest first. Yanked releases are left out.
+    pub fn published_versions(&self) -> Vec<(Version, String)> {
+        published_versions(
+            self.versions
+                .borrow()
+                .iter()
+                .map(|(_, release)| (release.yanked, release.version(), &release.num)),
+        )
     }
 }
 
+/// The versions of releases given as yanked flag, parsed version and number, highest first and
+/// without the yanked ones.
+pub fn published_versions<'n, I>(releases: I) -> Vec<(Version, String)>
+where
+    I: IntoIterator<Item = (bool, Option<Version>, &'n String)>,
+{
+    let mut published = releases
+        .into_iter()
+        .filter(|(yanked, _, _)| !yanked)
+        .filter_map(|(_, version, num)| Some((version?, num.clone())))
+        .collect::<Vec<_>>();
+    published.sort_unstable_by(|(left, _), (right, _)| right.cmp(left));
+    published
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Kiste {
     pub created_at: String,
@@ -50,16 +74,36 @@ pub struct Kiste {
     max_upload_size: Option<u32>,
     pub name: String,
     repository: String,
+    /// The registry the crate comes from, `None` for crates.io. In the dump of an alternate
+    /// registry it is the index URL of a crate listed from elsewhere.
+    #[serde(default)]
+    pub registry: Option<String>,
     updated_at: String,
 }
 
+impl Kiste {
+    /// A crate first seen in the registry index, which knows little more than its name.
+    pub fn indexed(id: u32, name: String) -> Self {
+        Self {
+            id,
+            name,
+            ..Self::default()
+        }
+    }
+
+    /// `created_at` is a Postgres timestamp, only the date part is of interest to queries.
+    pub fn created_on(&self) -> Option<NaiveDate> {
+        self.created_at.get(..10)?.parse().ok()
+    }
+}
+
+/// A row of `dependencies.csv`, only the columns the graph keeps.
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Depencil {
     pub crate_id: u32,
     default_features: Option<String>,
     explicit_name: Option<String>,
     features: Option<String>,
-    pub id: u32,
     kind: u32,
     optional: String,
     req: String,
@@ -67,112 +111,253 @@ pub struct Depencil {
     pub version_id: u32,
 }
 
-#[derive(Debug, Default, Deserialize)]
+/// A row of `versions.csv`, only the columns the graph keeps.
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct Lesart {
-    bin_names: String,
-    checksum: String,
     #[serde(default)]
     pub crate_id: Option<u32>,
-    crate_size: Option<u32>,
     created_at: String,
     downloads: u32,
     features: String,
-    has_lib: String,
     pub id: u32,
-    license: String,
-    links: String,
     num: String,
-    published_by: String,
-    rust_version: String,
-    updated_at: String,
     yanked: String,
 }
 
+/// A single published version of a crate together with the dependencies it declared.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Release {
+    pub id: u32,
+    pub num: String,
+    pub yanked: bool,
+    pub created_at: String,
+    pub downloads: u32,
+    pub features: BTreeMap<String, Vec<String>>,
+    pub dependencies: SichtCell<Vec<Skid>>,
+}
+
+impl Release {
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.num).ok()
+    }
+
+    pub fn add_dependency(&self, dependency: Skid) {
+        self.dependencies.borrow_mut().push(dependency);
+    }
+}
+
+impl From<Lesart> for Release {
+    fn from(lesart: Lesart) -> Self {
+        Self {
+            id: lesart.id,
+            num: lesart.num,
+            yanked: lesart.yanked == "t",
+            created_at: lesart.created_at,
+            downloads: lesart.downloads,
+            features: serde_json::from_str(&lesart.features).unwrap_or_default(),
+            dependencies: SichtCell::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    #[default]
+    Normal,
+    Build,
+    Dev,
+}
+
+impl DependencyKind {
+    #[must_use]
+    pub fn try_from_token(input: &str) -> Option<Self> {
+        match input {
+            "normal" | "NORMAL" => Some(DependencyKind::Normal),
+            "build" | "BUILD" => Some(DependencyKind::Build),
+            "dev" | "DEV" => Some(DependencyKind::Dev),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Build => "build",
+            DependencyKind::Dev => "dev",
+        }
+    }
+}
+
+/// `dependencies.csv` stores the kind as the index Cargo's registry uses.
+impl From<u32> for DependencyKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => DependencyKind::Build,
+            2 => DependencyKind::Dev,
+            _ => DependencyKind::Normal,
+        }
+    }
+}
+
+/// An edge of the graph: the crate depended upon and the requirement it was declared with.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Skid {
-    dependency: u32,
-    version: Option<String>,
+    pub dependency: u32,
+    pub version: Option<String>,
+    pub req: String,
+    pub kind: DependencyKind,
+    pub name: Option<String>,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    pub target: Option<String>,
 }
 
 impl Skid {
-    pub fn new(dependency: u32, version: String) -> Self {
+    pub fn new_with_dependency(dependency: u32, req: String, kind: DependencyKind) -> Self {
         Self {
             dependency,
-            version: Some(version),
+            version: None,
+            req,
+            kind,
+            name: None,
+            optional: false,
+            default_features: true,
+            features: Vec::default(),
+            target: None,
         }
     }
 
-    pub fn new_with_dependency(dependency: u32) -> Self {
+    /// Settles on the highest published version matching the requirement, the way Cargo would
+    /// for a fresh lockfile.
+    pub fn resolve(&mut self, published: &[(Version, String)]) {
+        self.version = VersionReq::parse(&self.req).ok().and_then(|req| {
+            published
+                .iter()
+                .find(|(version, _)| req.matches(version))
+                .map(|(_, num)| num.clone())
+        });
+    }
+
+    pub fn accepts_any(&self, versions: Option<&[Version]>) -> bool {
+        versions.is_none_or(|versions| {
+            VersionReq::parse(&self.req)
+                .is_ok_and(|req| versions.iter().any(|version| req.matches(version)))
+        })
+    }
+}
+
+impl From<&Depencil> for Skid {
+    fn from(depencil: &Depencil) -> Self {
         Self {
-            dependency,
-            version: None,
+            name: depencil
+                .explicit_name
+                .clone()
+                .filter(|name| !name.is_empty()),
+            optional: depencil.optional == "t",
+            default_features: depencil.default_features.as_deref() != Some("f"),
+            features: depencil
+                .features
+                .as_deref()
+                .map(parse_pg_array)
+                .unwrap_or_default(),
+            target: Some(depencil.target.clone()).filter(|target| !target.is_empty()),
+            ..Self::new_with_dependency(
+                depencil.crate_id,
+                depencil.req.clone(),
+                depencil.kind.into(),
+            )
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// Why a node of an `UnrolledCrate` was left without its dependents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Marker {
+    /// Unrolled earlier in the same tree.
+    Repeated,
+    /// Depends back on one of its own ancestors.
+    Cycle,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[non_exhaustive]
 pub struct UnrolledCrate {
     pub crate_id: u32,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    pub version: Option<String>,
+    pub req: Option<String>,
+    pub kind: Option<DependencyKind>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<Marker>,
     pub dependents: Vec<Self>,
 }
 impl UnrolledCrate {
-    pub fn new(crate_id: u32, name: String, dependents: Vec<Self>) -> Self {
+    #[must_use]
+    pub fn new(
+        crate_id: u32,
+        name: String,
+        version: Option<String>,
+        dependents: Vec<Self>,
+    ) -> Self {
         Self {
             crate_id,
             name,
+            registry: None,
+            version,
+            req: None,
+            kind: None,
+            features: Vec::default(),
+            marker: None,
             dependents,
         }
     }
 }
 
-impl<'de> Deserialize<'de> for Crate {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        struct CrateVisitor<'a> {
-            krate: PhantomData<&'a ()>,
-            dependencies: PhantomData<&'a ()>,
-        }
-
-        impl CrateVisitor<'_> {
-            fn new() -> Self {
-                Self {
-                    krate: PhantomData,
-                    dependencies: PhantomData,
-                }
-            }
-        }
-
-        impl Visitor<'_> for CrateVisitor<'_> {
-            type Value = Crate;
-
-            fn expecting(&self, formatter: &mut Formatter<'_>) -> core::fmt::Result {
-                write!(formatter, "Oder left or right are malformed")
-            }
-        }
-
-        deserializer.deserialize_struct("Crate", &["krate", "dependencies"], CrateVisitor::new())
-    }
+/// The dump writes array columns in Postgres' literal syntax, e.g. `{derive,"serde/std"}`.
+fn parse_pg_array(literal: &str) -> Vec<String> {
+    literal
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(|item| item.trim().trim_matches('"'))
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Cdv {
     pub crates: SichtMap<u32, String, Crate>,
-    pub dependencies: BTreeMap<u32, Vec<u32>>,
-    pub versions: BTreeMap<u32, u32>,
+    /// The edges next to the id of the release declaring them.
+    pub dependencies: Vec<(u32, Skid)>,
+    /// The releases next to the id of their crate, if the row named one.
+    pub releases: Vec<(Option<u32>, Release)>,
+    pub dumped_at: Option<String>,
+    pub tally: Tally,
 }
 impl Cdv {
-    pub fn process_to_carriage(self) -> Carriage {
+    /// Builds the graph, adding the rows that hang off nothing to the tally.
+    pub fn process_to_carriage(self) -> (Carriage, Tally) {
         let Cdv {
             crates,
             dependencies,
-            versions,
+            releases,
+            mut tally,
+            ..
         } = self;
 
         let carriage = Carriage::from_map(crates);
-        carriage.process_dependencies(&dependencies, &versions);
-        carriage
+        carriage.process_releases(releases, &mut tally.versions);
+        carriage.process_dependencies(dependencies, &mut tally.dependencies);
+        carriage.resolve_dependencies();
+        (carriage, tally)
     }
 }
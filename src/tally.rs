@@ -0,0 +1,191 @@
+use anyhow::{Result, bail};
+use std::fmt::{Display, Formatter};
+
+/// What became of the rows of one table of the dump. Skipped rows didn't deserialize, orphaned
+/// ones did but point at a crate or release the dump doesn't have.
+#[derive(Clone, Debug, Default)]
+pub struct TableTally {
+    pub table: &'static str,
+    pub read: usize,
+    pub skipped: usize,
+    pub orphaned: usize,
+    /// The first few reasons rows were dropped, enough to tell what changed.
+    pub samples: Vec<String>,
+}
+
+impl TableTally {
+    const SAMPLES: usize = 3;
+
+    pub fn new(table: &'static str) -> Self {
+        Self {
+            table,
+            ..Self::default()
+        }
+    }
+
+    pub fn skip<E: Display>(&mut self, error: &E) {
+        self.skipped += 1;
+        self.sample(error);
+    }
+
+    pub fn orphan<E: Display>(&mut self, reason: &E) {
+        self.orphaned += 1;
+        self.sample(reason);
+    }
+
+    fn sample<E: Display>(&mut self, reason: &E) {
+        if self.samples.len() < Self::SAMPLES {
+            self.samples.push(reason.to_string());
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.skipped == 0 && self.orphaned == 0
+    }
+}
+
+impl Display for TableTally {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} rows read, {} skipped, {} orphaned",
+            self.table, self.read, self.skipped, self.orphaned
+        )?;
+        for sample in &self.samples {
+            write!(f, "\n    {sample}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The ingestion report of a dump, one tally per table the graph is built from.
+#[derive(Clone, Debug)]
+pub struct Tally {
+    pub crates: TableTally,
+    pub versions: TableTally,
+    pub dependencies: TableTally,
+}
+
+impl Default for Tally {
+    fn default() -> Self {
+        Self {
+            crates: TableTally::new("crates.csv"),
+            versions: TableTally::new("versions.csv"),
+            dependencies: TableTally::new("dependencies.csv"),
+        }
+    }
+}
+
+impl Tally {
+    pub fn tables(&self) -> [&TableTally; 3] {
+        [&self.crates, &self.versions, &self.dependencies]
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.tables().iter().all(|table| table.is_clean())
+    }
+
+    /// Passes a clean dump. Otherwise the report is printed on stderr, or returned as the error
+    /// if `strict`, since a dump that drifted from the schema loads as a partial graph.
+    pub fn settle(&self, strict: bool) -> Result<()> {
+        if self.is_clean() {
+            Ok(())
+        } else if strict {
+            bail!("refusing a dump that doesn't load cleanly\n{self}")
+        } else {
+            eprintln!("the dump didn't load cleanly\n{self}");
+            Ok(())
+        }
+    }
+}
+
+impl Display for Tally {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [crates, versions, dependencies] = self.tables();
+        write!(f, "{crates}\n{versions}\n{dependencies}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::carriage::Carriage;
+    use crate::download::Config;
+    use crate::fixture::{self, DEPENDENCIES, VERSIONS, scratch};
+    use crate::fs::Mast;
+    use anyhow::Result;
+    use std::path::Path;
+
+    /// The fixture with a row of each table that doesn't deserialize or hangs off nothing.
+    fn write_unclean_dump(dump: &Path) -> Result<()> {
+        let versions = format!(
+            "{VERSIONS}\
+{{}},,2,,2024-01-01 00:00:00,many,{{}},t,22,MIT,,1.0.201,,,2024-01-01 00:00:00,f
+{{}},,9,,2024-01-01 00:00:00,10,{{}},t,90,MIT,,0.1.0,,,2024-01-01 00:00:00,f
+"
+        );
+        let dependencies = format!(
+            "{DEPENDENCIES}\
+1,t,,{{}},101,0,f,^1,,99
+9,t,,{{}},102,0,f,^1,,20
+"
+        );
+        fixture::write_tables(dump, fixture::CRATES, &versions, &dependencies)
+    }
+
+    #[test]
+    fn malformed_rows_are_tallied() -> Result<()> {
+        let dir = scratch("tally");
+        let dump = dir.join("db-dump.tar.gz");
+        write_unclean_dump(&dump)?;
+
+        let (carriage, tally) = Carriage::unarchive_tallied(&dump, false)?;
+        let strict = Carriage::unarchive(&dump, true);
+
+        assert_eq!(tally.crates.read, 2);
+        assert!(tally.crates.is_clean());
+        assert_eq!(
+            (
+                tally.versions.read,
+                tally.versions.skipped,
+                tally.versions.orphaned
+            ),
+            (5, 1, 1)
+        );
+        assert_eq!(
+            (
+                tally.dependencies.read,
+                tally.dependencies.skipped,
+                tally.dependencies.orphaned
+            ),
+            (3, 0, 2)
+        );
+        assert_eq!(tally.dependencies.samples.len(), 2);
+        assert!(strict.is_err_and(|error| error.to_string().contains("versions.csv: 5 rows")));
+        assert!(
+            carriage
+                .map
+                .borrow()
+                .get_with_base_key(&1)
+                .and_then(|serde| serde.release(&"1.0.200".to_owned()))
+                .is_some_and(|release| release.dependencies.borrow().len() == 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn strict_loads_refuse_a_cache_of_an_unclean_dump() -> Result<()> {
+        let dir = scratch("strict");
+        let dump = dir.join("db-dump.tar.gz");
+        write_unclean_dump(&dump)?;
+        let config = Config::default().with_cache_dir(dir.join("cache"));
+
+        let lenient = Mast::path(&dump).config(config.clone()).load();
+        let cached = Mast::path(&dump).config(config.clone()).load();
+        let strict = Mast::path(&dump).config(config.with_strict(true)).load();
+
+        assert!(lenient.is_ok_and(|carriage| !carriage.waybill.clean));
+        assert!(cached.is_ok());
+        assert!(strict.is_err_and(|error| error.to_string().contains("versions.csv: 5 rows")));
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::Result;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Identifies the dump a cache was built from, so a replaced `db-dump.tar.gz` is noticed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Waybill {
+    pub size: u64,
+    pub modified: u64,
+    pub sha256: String,
+    /// The `timestamp` crates.io wrote into the archive's `metadata.json`.
+    pub dumped_at: Option<String>,
+    /// The registries merged into the graph by name, each with the waybill of its source.
+    #[serde(default)]
+    pub registries: BTreeMap<String, Waybill>,
+    /// Whether every row of the dump, and of the registry dumps merged into it, loaded. A
+    /// strict load won't take a cache built otherwise.
+    #[serde(default)]
+    pub clean: bool,
+    /// The layout of the cache written along with this, see [`Waybill::FORMAT`].
+    #[serde(default)]
+    pub format: u32,
+}
+
+impl Waybill {
+    /// Bumped whenever what gets cached changes shape, so that caches of an earlier build are
+    /// rebuilt rather than misread.
+    pub const FORMAT: u32 = 2;
+
+    pub fn for_dump<P: AsRef<Path>>(path: P, dumped_at: Option<String>) -> Result<Self> {
+        let (size, modified) = Self::stat(path.as_ref())?;
+        Ok(Self {
+            size,
+            modified,
+            sha256: Self::hash(path.as_ref())?,
+            dumped_at,
+            registries: BTreeMap::new(),
+            clean: false,
+            format: Self::FORMAT,
+        })
+    }
+
+    /// Identifies an index checkout by the size of its files and the last time one was written.
+    /// Hashing every file of an index on each load would cost more than rebuilding.
+    pub fn for_checkout<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let mut waybill = Self::default();
+        waybill.add_checkout_files(root.as_ref())?;
+        Ok(waybill)
+    }
+
+    fn add_checkout_files(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                self.add_checkout_files(&path)?;
+            } else {
+                let (size, modified) = Self::stat(&path)?;
+                self.size += size;
+                self.modified = self.modified.max(modified);
+            }
+        }
+        Ok(())
+    }
+
+    fn stat(path: &Path) -> Result<(u64, u64)> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+        Ok((metadata.len(), modified))
+    }
+
+    /// The hex encoded SHA-256 of the file at `path`.
+    pub fn hash(path: &Path) -> Result<String> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hasher
+            .finalize()
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            }))
+    }
+
+    /// Whether the dump at `path` is still the one this was written for. Size and mtime settle
+    /// it cheaply, the content hash is only consulted when those moved, e.g. after a copy.
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+        if Self::stat(path)? == (self.size, self.modified) {
+            return Ok(true);
+        }
+        Ok(self.size == fs::metadata(path)?.len() && Self::hash(path)? == self.sha256)
+    }
+
+    /// How long ago the dump was taken, falling back to when the file was last written.
+    pub fn age(&self) -> Option<Duration> {
+        let taken = self
+            .dumped_at
+            .as_deref()
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .and_then(|timestamp| u64::try_from(timestamp.timestamp()).ok())
+            .unwrap_or(self.modified);
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(taken))
+            .ok()
+    }
+
+    pub fn describe_age(&self) -> String {
+        let when = self.dumped_at.as_deref().unwrap_or("an unknown date");
+        match self.age().map(|age| age.as_secs()) {
+            Some(secs) if secs < 2 * 3600 => format!("dump of {when}, {} minutes old", secs / 60),
+            Some(secs) if secs < 2 * 86400 => format!("dump of {when}, {} hours old", secs / 3600),
+            Some(secs) => format!("dump of {when}, {} days old", secs / 86400),
+            None => format!("dump of {when}"),
+        }
+    }
+}
+
+/// The part of the archive's `metadata.json` worth keeping.
+#[derive(Debug, Deserialize)]
+pub struct DumpMetadata {
+    pub timestamp: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::scratch;
+    use chrono::{Datelike, Timelike};
+
+    fn touch(path: &Path, modified: u64) -> Result<()> {
+        File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(modified))?;
+        Ok(())
+    }
+
+    #[test]
+    fn size_mtime_and_hash_tell_a_replaced_dump() -> Result<()> {
+        let dir = scratch("waybill");
+        let dump = dir.join("db-dump.tar.gz");
+        fs::write(&dump, b"the dump as it was")?;
+        let waybill = Waybill::for_dump(&dump, None)?;
+        assert!(waybill.matches(&dump)?);
+
+        // Size and mtime settle it without hashing.
+        let rehashed = Waybill {
+            sha256: "0".repeat(64),
+            ..waybill.clone()
+        };
+        assert!(rehashed.matches(&dump)?);
+
+        // Copied over: a new mtime, the same bytes.
+        touch(&dump, waybill.modified + 60)?;
+        assert!(waybill.matches(&dump)?);
+        assert!(!rehashed.matches(&dump)?);
+
+        // Replaced by a dump of the same size.
+        fs::write(&dump, b"the dump as it is!")?;
+        touch(&dump, waybill.modified + 120)?;
+        assert!(!waybill.matches(&dump)?);
+
+        // Replaced by one of another size, even if it kept the old mtime.
+        fs::write(&dump, b"a newer dump")?;
+        touch(&dump, waybill.modified)?;
+        assert!(!waybill.matches(&dump)?);
+        Ok(())
+    }
+
+    #[test]
+    fn age_is_told_in_the_largest_fitting_unit() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let ago = |secs: i64| now.checked_add_signed(-secs).unwrap();
+        let dumped = |secs: i64| {
+            let at = DateTime::from_timestamp(ago(secs).try_into().unwrap(), 0).unwrap();
+            let timestamp = format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                at.year(),
+                at.month(),
+                at.day(),
+                at.hour(),
+                at.minute(),
+                at.second()
+            );
+            Waybill {
+                dumped_at: Some(timestamp),
+                ..Waybill::default()
+            }
+        };
+
+        for (secs, age) in [
+            (30 * 60 + 5, "30 minutes old"),
+            (3 * 3600 + 5, "3 hours old"),
+            (5 * 86400 + 5, "5 days old"),
+        ] {
+            let waybill = dumped(secs);
+            let when = waybill.dumped_at.as_deref().unwrap();
+            assert_eq!(waybill.describe_age(), format!("dump of {when}, {age}"));
+        }
+
+        // A dump from the future has no age.
+        let waybill = dumped(-3600);
+        let when = waybill.dumped_at.as_deref().unwrap();
+        assert_eq!(waybill.describe_age(), format!("dump of {when}"));
+
+        // Without a timestamp the file's mtime stands in.
+        let modified = Waybill {
+            modified: ago(3 * 3600 + 5),
+            ..Waybill::default()
+        };
+        assert_eq!(modified.describe_age(), "dump of an unknown date, 3 hours old");
+    }
+}